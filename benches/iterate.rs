@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use clap::Parser;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::distributions::{Distribution, Uniform};
+use sppg::{
+    choose_word_list, cli::Args, iterate, lookup_word, passphrase::PassPhrase, roll_word_index,
+    WordList,
+};
+
+// The word lookup sppg used before the embedded lists became arrays: five
+// dice rolled into a String and looked up in a HashMap keyed by dice code.
+fn legacy_map(word_list: &dyn WordList) -> HashMap<String, String> {
+    (0..word_list.len())
+        .map(|i| {
+            (
+                word_list.dice_code(i).unwrap(),
+                word_list.word(i).unwrap().into(),
+            )
+        })
+        .collect()
+}
+
+fn legacy_lookup(map: &HashMap<String, String>) -> &str {
+    let mut lookup_number = String::new();
+    let dice = Uniform::from(1..7u32);
+    let mut rng = rand::thread_rng();
+    for _ in 0..5 {
+        let number = dice.sample(&mut rng);
+        lookup_number.push(char::from_digit(number, 10).unwrap());
+    }
+
+    map.get(&lookup_number).unwrap()
+}
+
+// sppg's iterate() before this change, without the capital and special
+// character options.
+fn legacy_iterate(args: &Args, map: &HashMap<String, String>) -> Vec<PassPhrase> {
+    let mut list = Vec::<PassPhrase>::new();
+    for _ in 0..args.num_of_pass {
+        let mut passphrase = PassPhrase::new(args.separator);
+        for _ in 0..args.word_count {
+            passphrase.push(legacy_lookup(map));
+        }
+        if passphrase.is_insecure() {
+            continue;
+        }
+        list.push(passphrase)
+    }
+
+    list
+}
+
+fn word_lookup(c: &mut Criterion) {
+    let args = Args::parse_from(["sppg"]);
+    let word_list = choose_word_list(&args).unwrap();
+    let map = legacy_map(word_list.as_ref());
+
+    let mut group = c.benchmark_group("word lookup");
+    group.bench_function("dice string and hashmap", |b| {
+        b.iter(|| black_box(legacy_lookup(&map)))
+    });
+    group.bench_function("array index", |b| {
+        b.iter(|| {
+            black_box(lookup_word(
+                roll_word_index(word_list.as_ref(), &mut rand::thread_rng()),
+                word_list.as_ref(),
+            ))
+        })
+    });
+    group.finish();
+}
+
+fn bulk_generation(c: &mut Criterion) {
+    let args = Args::parse_from(["sppg", "-n", "255", "-w", "8"]);
+    let word_list = choose_word_list(&args).unwrap();
+    let map = legacy_map(word_list.as_ref());
+
+    let mut group = c.benchmark_group("255 phrases of 8 words");
+    group.bench_function("legacy iterate", |b| {
+        b.iter(|| black_box(legacy_iterate(&args, &map)))
+    });
+    group.bench_function("iterate", |b| b.iter(|| black_box(iterate(&args))));
+    group.finish();
+}
+
+criterion_group!(benches, word_lookup, bulk_generation);
+criterion_main!(benches);
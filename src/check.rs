@@ -0,0 +1,452 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    capital_char_positions, choose_word_list,
+    cli::Args,
+    crack::{format_crack_times, AttackerModel},
+    decode::{decode, specials_of, Decoded, Found},
+    has_case, log2_choose,
+    passphrase::PassPhrase,
+    special_char_gaps, title_case, Error, Placement, WordList, BUILTIN_LISTS,
+};
+
+// What an attacker who knows how a phrase was put together still has to
+// guess: which words of the list, which letters were capitalized and which
+// special characters went where. Anything that isn't in the list has to be
+// brute forced a character at a time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strength {
+    pub list: String,
+    pub separator: Option<char>,
+    pub joined: bool,
+    pub tokens: usize,
+    pub words: usize,
+    pub word_bits: f64,
+    pub capitals: usize,
+    pub capital_bits: f64,
+    pub specials: usize,
+    pub special_bits: f64,
+    pub unknown: usize,
+    pub unknown_bits: f64,
+}
+
+impl Strength {
+    pub fn entropy(&self) -> f64 {
+        self.word_bits + self.capital_bits + self.special_bits + self.unknown_bits
+    }
+}
+
+// Estimates the strength of `phrase` against the list chosen by `cli_args`
+// or, if none was, against whichever built-in list knows the most of its
+// words.
+pub fn check_phrase(cli_args: &Args, phrase: &str) -> Result<Strength, Error> {
+    let specials = specials_of(cli_args);
+    let list_chosen = cli_args.eff || cli_args.list.is_some() || cli_args.wordlist.is_some();
+    if list_chosen {
+        let word_list = choose_word_list(cli_args)?;
+        return Ok(check(
+            word_list.as_ref(),
+            phrase,
+            cli_args.separator,
+            &specials,
+        ));
+    }
+
+    let mut best: Option<Strength> = None;
+    for word_list in BUILTIN_LISTS {
+        let strength = check(*word_list, phrase, cli_args.separator, &specials);
+        if best.as_ref().is_none_or(|b| strength.words > b.words) {
+            best = Some(strength);
+        }
+    }
+
+    Ok(best.unwrap())
+}
+
+// Each special character is taken to be one of `specials`, the set -s
+// drew it from.
+pub fn check<W: WordList + ?Sized>(
+    word_list: &W,
+    phrase: &str,
+    separator: Option<char>,
+    specials: &[char],
+) -> Strength {
+    let separator = separator.or_else(|| guess_separator(word_list, phrase, specials));
+    let decoded = decode(word_list, phrase, separator, specials);
+    // Without a separator, decode splits words written as in CamelCase
+    let joined = separator.is_none() && decoded.len() > phrase.split_whitespace().count();
+
+    // The phrase as it was before any capital or special character was
+    // added, to count the places they could have gone
+    let mut base = if joined {
+        PassPhrase::joined()
+    } else {
+        PassPhrase::new(separator)
+    };
+    let mut words = 0;
+    let mut capitals = 0;
+    let mut cases = Vec::new();
+    let mut in_word = 0;
+    let mut as_token = 0;
+    let mut unknown_bits = 0.0;
+    for Decoded { token, found } in &decoded {
+        match found {
+            Found::Word {
+                word,
+                specials: removed,
+                ..
+            } => {
+                base.push(word);
+                words += 1;
+                capitals += token
+                    .graphemes(true)
+                    .filter(|g| g.to_lowercase() != *g)
+                    .count();
+                in_word += removed.len();
+                let form: String = token.chars().filter(|ch| !removed.contains(ch)).collect();
+                cases.push(WordCase::of(word, &form));
+            }
+            Found::SpecialChar { .. } => as_token += 1,
+            Found::NotInList => {
+                base.push(token);
+                unknown_bits += brute_force_bits(token);
+            }
+        }
+    }
+
+    let special_count = in_word + as_token;
+    let placement = match (in_word, as_token) {
+        (_, 0) => Placement::Word,
+        (0, _) => Placement::Token,
+        _ => Placement::Any,
+    };
+    let special_bits = if special_count > 0 {
+        special_count as f64 * (specials.len() as f64).log2()
+            + log2_choose(special_char_gaps(&base, placement), special_count)
+    } else {
+        0.0
+    };
+
+    Strength {
+        list: word_list.name().into(),
+        separator,
+        joined,
+        tokens: decoded.len(),
+        words,
+        word_bits: words as f64 * word_list.entropy_per_word(),
+        capitals,
+        capital_bits: case_bits(&cases)
+            .unwrap_or(f64::INFINITY)
+            .min(log2_choose(capital_char_positions(&base), capitals)),
+        specials: special_count,
+        special_bits,
+        unknown: decoded.len() - words - as_token,
+        unknown_bits,
+    }
+}
+
+// How a word of the list was written, as far as --case can tell
+struct WordCase {
+    cased: bool,
+    lower: bool,
+    upper: bool,
+    title: bool,
+}
+
+impl WordCase {
+    fn of(word: &str, form: &str) -> Self {
+        Self {
+            cased: has_case(word),
+            lower: form == form.to_lowercase(),
+            upper: form == form.to_uppercase(),
+            title: form == title_case(&form.to_lowercase()),
+        }
+    }
+}
+
+// The bits of the --case style the words were written in, if they fit
+// one. An attacker tries the styles before single capitals, so whichever
+// costs the least is what the capitals are worth.
+fn case_bits(cases: &[WordCase]) -> Option<f64> {
+    let cased = cases.iter().filter(|case| case.cased).count();
+    let upper = cases.iter().filter(|case| !case.lower).count();
+    if cases.iter().all(|case| case.title) {
+        Some(0.0)
+    } else if upper == 1 && cases.iter().all(|case| case.lower || case.upper) {
+        Some((cased as f64).log2())
+    } else if cases.iter().all(|case| case.lower || case.upper) {
+        Some(cased as f64)
+    } else {
+        None
+    }
+}
+
+// Tries white space and then each symbol in the phrase as the separator,
+// and keeps the one that splits it into the most words of the list. None
+// means white space.
+fn guess_separator<W: WordList + ?Sized>(
+    word_list: &W,
+    phrase: &str,
+    specials: &[char],
+) -> Option<char> {
+    let mut candidates = vec![None];
+    for ch in phrase.chars() {
+        if !ch.is_alphanumeric() && !ch.is_whitespace() && !candidates.contains(&Some(ch)) {
+            candidates.push(Some(ch));
+        }
+    }
+
+    let words = |separator| {
+        decode(word_list, phrase, separator, specials)
+            .iter()
+            .filter(|d| matches!(d.found, Found::Word { .. }))
+            .count()
+    };
+    let mut best = (None, words(None));
+    for separator in candidates.into_iter().skip(1) {
+        let count = words(separator);
+        if count > best.1 {
+            best = (separator, count);
+        }
+    }
+
+    best.0
+}
+
+// Every character of `token` guessed from the classes of characters in it
+fn brute_force_bits(token: &str) -> f64 {
+    let mut pool = 0;
+    if token.chars().any(|c| c.is_lowercase()) {
+        pool += 26;
+    }
+    if token.chars().any(|c| c.is_uppercase()) {
+        pool += 26;
+    }
+    if token.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if token.chars().any(|c| c.is_ascii_punctuation()) {
+        pool += 32;
+    }
+    // Letters outside of ASCII, and anything else
+    if token
+        .chars()
+        .any(|c| !c.is_ascii() && !c.is_lowercase() && !c.is_uppercase())
+    {
+        pool += 100;
+    }
+
+    token.chars().count() as f64 * (pool.max(1) as f64).log2()
+}
+
+pub fn format_strength(strength: &Strength, attackers: &[AttackerModel]) -> String {
+    let separator = match strength.separator {
+        Some(ch) => format!("'{ch}'"),
+        None if strength.joined => "none, words joined".into(),
+        None => "white space".into(),
+    };
+    let mut text = format!("list: {}\n", strength.list);
+    text += &format!("separator: {separator}\n");
+    text += &format!(
+        "words in the list: {} of {} ({:.1} bits)\n",
+        strength.words, strength.tokens, strength.word_bits
+    );
+    if strength.capitals > 0 {
+        text += &format!(
+            "capital letters: {} ({:.1} bits)\n",
+            strength.capitals, strength.capital_bits
+        );
+    }
+    if strength.specials > 0 {
+        text += &format!(
+            "special characters: {} ({:.1} bits)\n",
+            strength.specials, strength.special_bits
+        );
+    }
+    if strength.unknown > 0 {
+        text += &format!(
+            "not in the list: {} ({:.1} bits by brute force)\n",
+            strength.unknown, strength.unknown_bits
+        );
+    }
+    text += &format!("entropy: {:.1} bits\n", strength.entropy());
+    text += "time to crack:";
+    text += &format_crack_times(strength.entropy(), attackers);
+    text += "\n";
+
+    text
+}
+
+// Reads one line from stdin. At a terminal the user is prompted and what
+// they type isn't echoed, so the phrase doesn't end up on the screen.
+pub fn read_passphrase() -> io::Result<String> {
+    let stdin = io::stdin();
+    let mut line = String::new();
+    if stdin.is_terminal() {
+        eprint!("passphrase: ");
+        io::stderr().flush()?;
+        let echo = EchoOff::new()?;
+        stdin.lock().read_line(&mut line)?;
+        drop(echo);
+        eprintln!();
+    } else {
+        stdin.lock().read_line(&mut line)?;
+    }
+
+    Ok(line.trim_end_matches(['\r', '\n']).into())
+}
+
+// Turns off the terminal's echo until it is dropped
+struct EchoOff {
+    #[cfg(unix)]
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl EchoOff {
+    fn new() -> io::Result<Self> {
+        // SAFETY: tcgetattr() fills in the termios struct, which is plain
+        // data, and tcsetattr() only reads it
+        unsafe {
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut silent = saved;
+            silent.c_lflag &= !libc::ECHO;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { saved })
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in new()
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+// Elsewhere the phrase is echoed as it is typed
+#[cfg(not(unix))]
+impl EchoOff {
+    fn new() -> io::Result<Self> {
+        Ok(Self {})
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{wordlist::special_char::special_char_set, EFF, ORIGINAL};
+
+    fn dice() -> Vec<char> {
+        special_char_set(None)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn check_plain_phrase() {
+        let strength = check(&ORIGINAL, "a levi norm apathy", None, &dice());
+
+        assert_eq!(strength.separator, None);
+        assert_eq!((strength.words, strength.tokens), (4, 4));
+        assert!(close(strength.entropy(), 4.0 * 7776f64.log2()));
+    }
+
+    #[test]
+    fn check_guesses_separator() {
+        let strength = check(&EFF, "zoom-abacus-passing", None, &dice());
+
+        assert_eq!(strength.separator, Some('-'));
+        assert_eq!(strength.words, 3);
+    }
+
+    #[test]
+    fn check_splits_camel_case() {
+        let strength = check(&EFF, "ZoomAbacusPassing", None, &dice());
+
+        assert!(strength.joined);
+        assert_eq!((strength.words, strength.tokens), (3, 3));
+        assert!(close(strength.entropy(), 3.0 * 7776f64.log2()));
+    }
+
+    #[test]
+    fn check_counts_capital_and_special_char() {
+        // "zoomabacuspassing" has 17 letters and 3 + 17 = 20 gaps
+        let strength = check(&EFF, "zoom abaCus pas$sing", None, &dice());
+
+        assert_eq!((strength.capitals, strength.specials), (1, 1));
+        assert!(close(strength.capital_bits, 17f64.log2()));
+        assert!(close(strength.special_bits, 36f64.log2() + 20f64.log2()));
+    }
+
+    #[test]
+    fn check_counts_case_styles_as_such() {
+        let title = check(&EFF, "Zoom Abacus Passing", None, &dice());
+        assert_eq!(title.capitals, 3);
+        assert_eq!(title.capital_bits, 0.0);
+
+        let upper_word = check(&EFF, "zoom ABACUS passing", None, &dice());
+        assert!(close(upper_word.capital_bits, 3f64.log2()));
+
+        let random = check(&EFF, "ZOOM ABACUS passing", None, &dice());
+        assert!(close(random.capital_bits, 3.0));
+    }
+
+    #[test]
+    fn check_special_char_token() {
+        // 3 words have 4 gaps between and around them
+        let strength = check(&EFF, "zoom abacus ( passing", None, &dice());
+
+        assert_eq!(
+            (strength.words, strength.specials, strength.unknown),
+            (3, 1, 0)
+        );
+        assert!(close(strength.special_bits, 36f64.log2() + 4f64.log2()));
+    }
+
+    #[test]
+    fn check_special_chars_of_another_set() {
+        let digits = special_char_set(Some("digits-only"));
+        let strength = check(&EFF, "zoom abacus 7 passing", None, &digits);
+
+        assert_eq!(strength.specials, 1);
+        assert!(close(strength.special_bits, 10f64.log2() + 4f64.log2()));
+    }
+
+    #[test]
+    fn check_brute_forces_unknown_words() {
+        let strength = check(&EFF, "zoom Xyzzy", None, &dice());
+
+        assert_eq!((strength.words, strength.unknown), (1, 1));
+        assert!(close(strength.unknown_bits, 5.0 * 52f64.log2()));
+    }
+
+    #[test]
+    fn check_picks_list_with_most_words() {
+        let cli_args = Args::parse_from(["sppg"]);
+        let strength = check_phrase(&cli_args, "abacus zoom passing").unwrap();
+
+        assert_eq!(strength.list, "eff");
+    }
+
+    #[test]
+    fn log2_choose_counts() {
+        assert!(close(log2_choose(10, 0), 0.0));
+        assert!(close(log2_choose(10, 1), 10f64.log2()));
+        assert!(close(log2_choose(10, 2), 45f64.log2()));
+    }
+}
@@ -1,22 +1,105 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{builder::NonEmptyStringValueParser, Parser, Subcommand};
+
+use crate::{policy::Policy, rng::RngSource, Case, Placement};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    #[arg(short, long)]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[arg(short, long, global = true)]
     pub eff: bool,
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "eff")]
+    pub wordlist: Option<PathBuf>,
+    #[arg(short, long, global = true, value_name = "NAME", conflicts_with_all = ["eff", "wordlist"])]
+    pub list: Option<String>,
     #[arg(short, long, default_value_t = 6, value_parser = clap::value_parser!(u8).range(1..))]
     pub num_of_pass: u8,
-    #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(1..))]
+    #[arg(short, long, global = true, default_value_t = 5, value_parser = clap::value_parser!(u8).range(1..))]
     pub word_count: u8,
-    #[arg(short = 'c', long, default_value_if("quality", "true", Some("true")))]
+    #[arg(
+        long,
+        global = true,
+        value_name = "BITS",
+        conflicts_with = "word_count",
+        value_parser = clap::value_parser!(u16).range(1..=1024)
+    )]
+    pub min_entropy: Option<u16>,
+    #[arg(long, global = true, value_enum, default_value_t = Policy::Entropy)]
+    pub policy: Policy,
+    #[arg(long)]
+    pub explain_policy: bool,
+    #[arg(short = 'c', long, global = true)]
     pub use_capital_char: bool,
-    #[arg(short = 's', long, default_value_if("quality", "true", Some("true")))]
+    #[arg(long, global = true, value_name = "N")]
+    pub capitals: Option<u8>,
+    #[arg(long, global = true, value_enum, value_name = "STYLE", default_value_t = Case::Lower)]
+    pub case: Case,
+    #[arg(short = 's', long, global = true)]
     pub use_special_char: bool,
-    #[arg(short, long)]
+    #[arg(long, global = true, value_name = "N")]
+    pub specials: Option<u8>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "SET",
+        value_parser = NonEmptyStringValueParser::new()
+    )]
+    pub special_chars: Option<String>,
+    #[arg(long, global = true, value_name = "N", default_value_t = 0)]
+    pub digits: u8,
+    #[arg(long, global = true, value_enum, value_name = "MODE", default_value_t = Placement::Word)]
+    pub special_placement: Placement,
+    #[arg(short, long, global = true)]
     pub quality: bool,
-    #[arg(short = 'S', long)]
+    #[arg(long, global = true, value_name = "RULES")]
+    pub rules: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "CHARS",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    pub max_length: Option<u16>,
+    #[arg(short = 'S', long, global = true)]
     pub separator: Option<char>,
+    #[arg(long, global = true)]
+    pub show_entropy: bool,
+    #[arg(long, global = true)]
+    pub show_rolls: bool,
+    #[arg(long, global = true)]
+    pub show_crack_time: bool,
+    #[arg(long, global = true, value_name = "PATH")]
+    pub attackers: Option<PathBuf>,
+    #[arg(long, value_enum, value_name = "SOURCE", default_value_t = RngSource::Thread)]
+    pub rng: RngSource,
+    #[arg(long, conflicts_with = "rng")]
+    pub seed: Option<u64>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    #[command(about = "Make one passphrase from rolls of physical dice")]
+    Dice,
+    #[command(about = "Find the dice code of each word of a passphrase")]
+    Decode { phrase: String },
+    #[command(about = "Estimate the strength of a passphrase read from stdin")]
+    Check,
+}
+
+// -q implies -c and -s, and --special-chars implies -s. They are worked
+// out here rather than by clap, which loses what a global flag implies when
+// it is given before the subcommand.
+impl Args {
+    pub fn capital_char(&self) -> bool {
+        self.use_capital_char || self.quality
+    }
+
+    pub fn special_char(&self) -> bool {
+        self.use_special_char || self.quality || self.special_chars.is_some()
+    }
 }
 
 pub fn process_command_line() -> Args {
@@ -96,6 +179,53 @@ mod test {
         assert_eq!(value, 5, "default -w value is 5");
     }
 
+    #[test]
+    fn verify_cli_arg_min_entropy_is_set() {
+        let value = Args::try_parse_from(["sppg", "--min-entropy", "60"])
+            .expect("this command is supposed to work")
+            .min_entropy;
+
+        assert_eq!(value, Some(60), "Arg --min-entropy is set to 60");
+    }
+
+    #[test]
+    fn verify_cli_arg_min_entropy_conflicts_with_w() {
+        assert_eq!(
+            Args::try_parse_from(["sppg", "--min-entropy", "60", "-w", "4"])
+                .expect_err("this command is supposed to fail")
+                .kind(),
+            clap::error::ErrorKind::ArgumentConflict,
+            "--min-entropy can't be used with -w"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_policy_defaults_to_entropy() {
+        let value = Args::try_parse_from(["sppg"])
+            .expect("this command is supposed to work")
+            .policy;
+
+        assert_eq!(value, Policy::Entropy, "default --policy is entropy");
+    }
+
+    #[test]
+    fn verify_cli_arg_policy_is_legacy() {
+        let value = Args::try_parse_from(["sppg", "--policy", "legacy"])
+            .expect("this command is supposed to work")
+            .policy;
+
+        assert_eq!(value, Policy::Legacy, "Arg --policy is legacy");
+    }
+
+    #[test]
+    fn verify_cli_arg_explain_policy_is_true() {
+        let value = Args::try_parse_from(["sppg", "--explain-policy"])
+            .expect("this command is supposed to work")
+            .explain_policy;
+
+        assert!(value, "Arg --explain-policy is set to true");
+    }
+
     #[test]
     fn verify_cli_arg_e_defaults_to_false() {
         let value = Args::try_parse_from(["sppg"])
@@ -114,6 +244,59 @@ mod test {
         assert!(value, "Arg -e is set to true");
     }
 
+    #[test]
+    fn verify_cli_arg_wordlist_defaults_to_none() {
+        let value = Args::try_parse_from(["sppg"])
+            .expect("this command is supposed to work")
+            .wordlist;
+
+        assert_eq!(value, None, "default --wordlist value is None");
+    }
+
+    #[test]
+    fn verify_cli_arg_wordlist_is_path() {
+        let value = Args::try_parse_from(["sppg", "--wordlist", "my.list"])
+            .expect("this command is supposed to work")
+            .wordlist;
+
+        assert_eq!(
+            value,
+            Some(PathBuf::from("my.list")),
+            "Arg --wordlist is set"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_wordlist_conflicts_with_e() {
+        assert_eq!(
+            Args::try_parse_from(["sppg", "--eff", "--wordlist", "my.list"])
+                .expect_err("this command is supposed to fail")
+                .kind(),
+            clap::error::ErrorKind::ArgumentConflict,
+            "--wordlist can't be used with --eff"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_list_is_name() {
+        let value = Args::try_parse_from(["sppg", "--list", "eff"])
+            .expect("this command is supposed to work")
+            .list;
+
+        assert_eq!(value.as_deref(), Some("eff"), "Arg --list is set");
+    }
+
+    #[test]
+    fn verify_cli_arg_list_conflicts_with_wordlist() {
+        assert_eq!(
+            Args::try_parse_from(["sppg", "-l", "eff", "--wordlist", "my.list"])
+                .expect_err("this command is supposed to fail")
+                .kind(),
+            clap::error::ErrorKind::ArgumentConflict,
+            "--list can't be used with --wordlist"
+        );
+    }
+
     #[test]
     fn verify_cli_arg_s_is_true01() {
         let value = Args::try_parse_from(["sppg", "--use-special-char"])
@@ -150,6 +333,28 @@ mod test {
         assert!(value, "Arg -c is set to true");
     }
 
+    #[test]
+    fn verify_cli_arg_special_placement_defaults_to_word() {
+        let value = Args::try_parse_from(["sppg"])
+            .expect("this command is supposed to work")
+            .special_placement;
+
+        assert_eq!(
+            value,
+            Placement::Word,
+            "default --special-placement is word"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_special_placement_is_token() {
+        let value = Args::try_parse_from(["sppg", "--special-placement", "token"])
+            .expect("this command is supposed to work")
+            .special_placement;
+
+        assert_eq!(value, Placement::Token, "Arg --special-placement is token");
+    }
+
     #[test]
     fn verify_cli_arg_q_is_true01() {
         let value = Args::try_parse_from(["sppg", "--quality"])
@@ -172,15 +377,274 @@ mod test {
     fn verify_cli_arg_q_implies_c_s() {
         let value_cap = Args::try_parse_from(["sppg", "-q"])
             .expect("this command is supposed to work")
-            .use_capital_char;
+            .capital_char();
         let value_special = Args::try_parse_from(["sppg", "-q"])
             .expect("this command is supposed to work")
-            .use_special_char;
+            .special_char();
 
         assert!(value_cap, "Arg -q implies -c");
         assert!(value_special, "Arg -q implies -s");
     }
 
+    #[test]
+    fn verify_cli_arg_q_before_subcommand_implies_c_s() {
+        let args =
+            Args::try_parse_from(["sppg", "-q", "dice"]).expect("this command is supposed to work");
+
+        assert_eq!(args.command, Some(Command::Dice), "the subcommand is dice");
+        assert!(args.capital_char(), "Arg -q implies -c before dice");
+        assert!(args.special_char(), "Arg -q implies -s before dice");
+    }
+
+    #[test]
+    fn verify_cli_arg_max_length_is_set() {
+        let value = Args::try_parse_from(["sppg", "--max-length", "20"])
+            .expect("this command is supposed to work")
+            .max_length;
+
+        assert_eq!(value, Some(20), "Arg --max-length is set to 20");
+    }
+
+    #[test]
+    fn verify_cli_arg_max_length_zero_is_error() {
+        assert_eq!(
+            Args::try_parse_from(["sppg", "--max-length", "0"])
+                .expect_err("this command is supposed to fail")
+                .kind(),
+            clap::error::ErrorKind::ValueValidation,
+            "if --max-length is zero the program returns a ValueValidation error"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_special_chars_implies_s() {
+        let args = Args::try_parse_from(["sppg", "--special-chars", "url-safe"])
+            .expect("this command is supposed to work");
+
+        assert_eq!(args.special_chars.as_deref(), Some("url-safe"));
+        assert!(args.special_char(), "Arg --special-chars implies -s");
+        assert!(
+            !Args::try_parse_from(["sppg"]).unwrap().special_char(),
+            "Arg -s is off without --special-chars"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_special_chars_before_subcommand_implies_s() {
+        let args = Args::try_parse_from(["sppg", "--special-chars", "#!", "dice"])
+            .expect("this command is supposed to work");
+
+        assert_eq!(args.special_chars.as_deref(), Some("#!"));
+        assert!(args.special_char(), "Arg --special-chars implies -s");
+    }
+
+    #[test]
+    fn verify_cli_arg_special_chars_empty_is_error() {
+        assert_eq!(
+            Args::try_parse_from(["sppg", "--special-chars", ""])
+                .expect_err("this command is supposed to fail")
+                .kind(),
+            clap::error::ErrorKind::InvalidValue,
+            "if --special-chars is empty the program returns an InvalidValue error"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_counts_are_set() {
+        let args = Args::try_parse_from([
+            "sppg",
+            "--capitals",
+            "2",
+            "--specials",
+            "0",
+            "--digits",
+            "3",
+        ])
+        .expect("this command is supposed to work");
+
+        assert_eq!(args.capitals, Some(2), "Arg --capitals is set");
+        assert_eq!(args.specials, Some(0), "Arg --specials is set");
+        assert_eq!(args.digits, 3, "Arg --digits is set");
+    }
+
+    #[test]
+    fn verify_cli_arg_counts_default_to_none() {
+        let args = Args::try_parse_from(["sppg"]).expect("this command is supposed to work");
+
+        assert_eq!(args.capitals, None, "Arg --capitals defaults to -c");
+        assert_eq!(args.specials, None, "Arg --specials defaults to -s");
+        assert_eq!(args.digits, 0, "Arg --digits defaults to 0");
+    }
+
+    #[test]
+    fn verify_cli_arg_case_defaults_to_lower() {
+        let value = Args::try_parse_from(["sppg"])
+            .expect("this command is supposed to work")
+            .case;
+
+        assert_eq!(value, Case::Lower, "default --case value is lower");
+    }
+
+    #[test]
+    fn verify_cli_arg_case_is_upper_word() {
+        let value = Args::try_parse_from(["sppg", "--case", "upper-word"])
+            .expect("this command is supposed to work")
+            .case;
+
+        assert_eq!(value, Case::UpperWord, "Arg --case is set to upper-word");
+    }
+
+    #[test]
+    fn verify_cli_arg_rules_is_set() {
+        let value = Args::try_parse_from(["sppg", "--rules", "minlength: 12; required: digit"])
+            .expect("this command is supposed to work")
+            .rules;
+
+        assert_eq!(
+            value.as_deref(),
+            Some("minlength: 12; required: digit"),
+            "Arg --rules is set"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_show_entropy_is_true() {
+        let value = Args::try_parse_from(["sppg", "--show-entropy"])
+            .expect("this command is supposed to work")
+            .show_entropy;
+
+        assert!(value, "Arg --show-entropy is set to true");
+    }
+
+    #[test]
+    fn verify_cli_arg_rng_defaults_to_thread() {
+        let value = Args::try_parse_from(["sppg"])
+            .expect("this command is supposed to work")
+            .rng;
+
+        assert_eq!(value, RngSource::Thread, "default --rng is thread");
+    }
+
+    #[test]
+    fn verify_cli_arg_rng_is_os() {
+        let value = Args::try_parse_from(["sppg", "--rng", "os"])
+            .expect("this command is supposed to work")
+            .rng;
+
+        assert_eq!(value, RngSource::Os, "Arg --rng is os");
+    }
+
+    #[test]
+    fn verify_cli_arg_seed_is_set() {
+        let value = Args::try_parse_from(["sppg", "--seed", "42"])
+            .expect("this command is supposed to work")
+            .seed;
+
+        assert_eq!(value, Some(42), "Arg --seed is set to 42");
+    }
+
+    #[test]
+    fn verify_cli_arg_seed_conflicts_with_rng() {
+        assert_eq!(
+            Args::try_parse_from(["sppg", "--seed", "42", "--rng", "os"])
+                .expect_err("this command is supposed to fail")
+                .kind(),
+            clap::error::ErrorKind::ArgumentConflict,
+            "--seed can't be used with --rng"
+        );
+    }
+
+    #[test]
+    fn verify_cli_no_command_by_default() {
+        let value = Args::try_parse_from(["sppg"])
+            .expect("this command is supposed to work")
+            .command;
+
+        assert_eq!(value, None, "there is no subcommand by default");
+    }
+
+    #[test]
+    fn verify_cli_dice_takes_options() {
+        let args = Args::try_parse_from(["sppg", "dice", "-e", "-w", "4", "-q"])
+            .expect("this command is supposed to work");
+
+        assert_eq!(args.command, Some(Command::Dice), "the subcommand is dice");
+        assert!(args.eff, "Arg -e is set after dice");
+        assert_eq!(args.word_count, 4, "Arg -w is set after dice");
+        assert!(args.capital_char(), "Arg -q implies -c after dice");
+        assert!(args.special_char(), "Arg -q implies -s after dice");
+    }
+
+    #[test]
+    fn verify_cli_arg_show_rolls_is_true() {
+        let value = Args::try_parse_from(["sppg", "--show-rolls"])
+            .expect("this command is supposed to work")
+            .show_rolls;
+
+        assert!(value, "Arg --show-rolls is set to true");
+    }
+
+    #[test]
+    fn verify_cli_decode_takes_phrase() {
+        let value = Args::try_parse_from(["sppg", "decode", "-e", "some phrase"])
+            .expect("this command is supposed to work")
+            .command;
+
+        assert_eq!(
+            value,
+            Some(Command::Decode {
+                phrase: "some phrase".into()
+            }),
+            "the subcommand is decode"
+        );
+    }
+
+    #[test]
+    fn verify_cli_decode_needs_phrase() {
+        assert_eq!(
+            Args::try_parse_from(["sppg", "decode"])
+                .expect_err("this command is supposed to fail")
+                .kind(),
+            clap::error::ErrorKind::MissingRequiredArgument,
+            "decode needs a phrase"
+        );
+    }
+
+    #[test]
+    fn verify_cli_check_takes_no_phrase() {
+        let value = Args::try_parse_from(["sppg", "check", "-e"])
+            .expect("this command is supposed to work")
+            .command;
+
+        assert_eq!(value, Some(Command::Check), "the subcommand is check");
+        assert!(
+            Args::try_parse_from(["sppg", "check", "some phrase"]).is_err(),
+            "the phrase is only read from stdin"
+        );
+    }
+
+    #[test]
+    fn verify_cli_arg_show_crack_time_is_true() {
+        let value = Args::try_parse_from(["sppg", "--show-crack-time"])
+            .expect("this command is supposed to work")
+            .show_crack_time;
+
+        assert!(value, "Arg --show-crack-time is set to true");
+    }
+
+    #[test]
+    fn verify_cli_arg_attackers_is_path() {
+        let value = Args::try_parse_from(["sppg", "check", "--attackers", "my.conf"])
+            .expect("this command is supposed to work")
+            .attackers;
+
+        assert_eq!(
+            value,
+            Some(PathBuf::from("my.conf")),
+            "Arg --attackers is set"
+        );
+    }
+
     #[test]
     fn verify_cli_arg_separator_is_true02() {
         let value = Args::try_parse_from(["sppg", "-S", "-"])
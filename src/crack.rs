@@ -0,0 +1,249 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+// How fast an attacker can try guesses
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackerModel {
+    pub name: String,
+    pub guesses_per_second: f64,
+}
+
+// Rough rates for a well funded attacker. The online rate is that of a
+// service that limits failed logins; the offline rates are for a rig of
+// GPUs against a stolen hash, from a fast unsalted one such as SHA-256 to
+// bcrypt (cost 12) and Argon2id as recommended by OWASP.
+const DEFAULT_MODELS: [(&str, f64); 4] = [
+    ("online-throttled", 100.0 / 3600.0),
+    ("offline-fast-hash", 1e11),
+    ("offline-bcrypt", 1e4),
+    ("offline-argon2id", 1e3),
+];
+
+const CONFIG_FILE: &str = "sppg/attackers.conf";
+
+pub fn default_models() -> Vec<AttackerModel> {
+    DEFAULT_MODELS
+        .iter()
+        .map(|(name, rate)| AttackerModel {
+            name: (*name).into(),
+            guesses_per_second: *rate,
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "unable to read {}: {e}", path.display()),
+            ConfigError::Parse { path, line, reason } => {
+                write!(f, "{}, line {line}: {reason}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// The default models with any rates set in the config file. The file is
+// `path` if one is given, otherwise attackers.conf in the user's config
+// directory, which doesn't have to exist.
+pub fn load_models(path: Option<&Path>) -> Result<Vec<AttackerModel>, ConfigError> {
+    let (path, required) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => match config_dir() {
+            Some(dir) => (dir.join(CONFIG_FILE), false),
+            None => return Ok(default_models()),
+        },
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if !required && e.kind() == io::ErrorKind::NotFound => return Ok(default_models()),
+        Err(e) => return Err(ConfigError::Io(path, e)),
+    };
+
+    parse_models(&contents).map_err(|(line, reason)| ConfigError::Parse { path, line, reason })
+}
+
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+}
+
+// Reads lines of `name = guesses per second`. A name that isn't one of the
+// defaults adds a model of its own.
+pub fn parse_models(contents: &str) -> Result<Vec<AttackerModel>, (usize, String)> {
+    let mut models = default_models();
+    for (idx, line) in contents.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((name, rate)) = line.split_once('=') else {
+            return Err((
+                line_no,
+                format!("expected 'name = guesses per second', found '{line}'"),
+            ));
+        };
+        let name = name.trim();
+        let rate = rate.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err((line_no, format!("invalid attacker name '{name}'")));
+        }
+        let guesses_per_second = match rate.parse::<f64>() {
+            Ok(rate) if rate.is_finite() && rate > 0.0 => rate,
+            _ => {
+                return Err((
+                    line_no,
+                    format!("invalid number of guesses per second '{rate}'"),
+                ))
+            }
+        };
+
+        match models.iter_mut().find(|m| m.name == name) {
+            Some(model) => model.guesses_per_second = guesses_per_second,
+            None => models.push(AttackerModel {
+                name: name.into(),
+                guesses_per_second,
+            }),
+        }
+    }
+
+    Ok(models)
+}
+
+// On average an attacker has to try half of the phrases before finding
+// the right one.
+pub fn seconds_to_crack(bits: f64, model: &AttackerModel) -> f64 {
+    (bits - 1.0).exp2() / model.guesses_per_second
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    const UNITS: [(&str, f64); 5] = [
+        ("year", 365.25 * 86400.0),
+        ("day", 86400.0),
+        ("hour", 3600.0),
+        ("minute", 60.0),
+        ("second", 1.0),
+    ];
+    if seconds < 1.0 {
+        return "less than a second".into();
+    }
+    let years = seconds / UNITS[0].1;
+    if years >= 1e6 {
+        return format!("{years:.1e} years");
+    }
+
+    let unit_of = |seconds| UNITS.iter().find(|(_, length)| seconds >= *length).unwrap();
+    // Rounding may make a whole one of the next unit, as 59.6 seconds does
+    let (_, length) = unit_of(seconds);
+    let rounded = (seconds / length).round() * length;
+    let (unit, length) = unit_of(rounded);
+    let count = (rounded / length).round();
+    if count == 1.0 {
+        format!("1 {unit}")
+    } else {
+        format!("{count} {unit}s")
+    }
+}
+
+// One line per model, indented to go under a phrase
+pub fn format_crack_times(bits: f64, models: &[AttackerModel]) -> String {
+    let width = models.iter().map(|m| m.name.len()).max().unwrap_or(0);
+    let mut text = String::new();
+    for model in models {
+        let time = format_duration(seconds_to_crack(bits, model));
+        text += &format!("\n    {:<width$}  {time}", model.name);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_overrides_and_adds_models() {
+        let contents = "# a faster attacker\n\noffline-bcrypt = 2e5\nmy-laptop=1000\n";
+        let models = parse_models(contents).unwrap();
+
+        assert_eq!(models.len(), 5);
+        assert_eq!(models[2].name, "offline-bcrypt");
+        assert_eq!(models[2].guesses_per_second, 2e5);
+        assert_eq!(models[4].name, "my-laptop");
+        assert_eq!(models[4].guesses_per_second, 1000.0);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(
+            parse_models("\nonline-throttled 10\n").unwrap_err(),
+            (
+                2,
+                "expected 'name = guesses per second', found 'online-throttled 10'".into()
+            )
+        );
+        assert_eq!(
+            parse_models("a = -1").unwrap_err(),
+            (1, "invalid number of guesses per second '-1'".into())
+        );
+        assert_eq!(
+            parse_models("a b = 1").unwrap_err(),
+            (1, "invalid attacker name 'a b'".into())
+        );
+    }
+
+    #[test]
+    fn missing_config_given_is_an_error() {
+        let err = load_models(Some(Path::new("no/such/attackers.conf"))).unwrap_err();
+
+        assert!(matches!(err, ConfigError::Io(..)));
+    }
+
+    #[test]
+    fn crack_time_is_half_the_space() {
+        let model = AttackerModel {
+            name: "test".into(),
+            guesses_per_second: 1000.0,
+        };
+
+        assert_eq!(seconds_to_crack(11.0, &model), 1.024);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(0.5), "less than a second");
+        assert_eq!(format_duration(1.2), "1 second");
+        assert_eq!(format_duration(40.0), "40 seconds");
+        assert_eq!(format_duration(59.6), "1 minute");
+        assert_eq!(format_duration(90.0), "2 minutes");
+        assert_eq!(format_duration(3599.6), "1 hour");
+        assert_eq!(format_duration(7200.0), "2 hours");
+        assert_eq!(format_duration(3.0 * 86400.0), "3 days");
+        assert_eq!(format_duration(400.0 * 86400.0), "1 year");
+        assert_eq!(format_duration(3.2e15 * 365.25 * 86400.0), "3.2e15 years");
+    }
+}
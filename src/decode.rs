@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    choice_roll, choose_word_list, cli::Args, word_roll, wordlist::special_char::special_char_set,
+    Error, WordList,
+};
+
+// What one word of a phrase turned out to be
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Found {
+    // A word of the list, with the capitals and special characters that
+    // had to be undone to find it
+    Word {
+        word: String,
+        code: String,
+        capital: bool,
+        specials: Vec<char>,
+    },
+    // A special character inserted as a word of its own
+    SpecialChar {
+        code: String,
+    },
+    NotInList,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decoded {
+    pub token: String,
+    pub found: Found,
+}
+
+// Looks up each word of `phrase` in the list chosen by `cli_args`.
+pub fn decode_phrase(cli_args: &Args, phrase: &str) -> Result<Vec<Decoded>, Error> {
+    let word_list = choose_word_list(cli_args)?;
+
+    let specials = specials_of(cli_args);
+
+    Ok(decode(
+        word_list.as_ref(),
+        phrase,
+        cli_args.separator,
+        &specials,
+    ))
+}
+
+// The characters that may have been added to the words: the set -s drew
+// from, less the separator, which phrases are made without, then the
+// digits of --digits, which the set leaves to it.
+pub(crate) fn specials_of(cli_args: &Args) -> Vec<char> {
+    let mut specials = special_char_set(cli_args.special_chars.as_deref());
+    specials.retain(|ch| Some(*ch) != cli_args.separator);
+    if cli_args.digits > 0 {
+        specials.retain(|ch| !ch.is_ascii_digit());
+        specials.extend('0'..='9');
+    }
+
+    specials
+}
+
+// Splits `phrase` on `separator`, or on white space if there is none, and
+// finds each word in the list. A word that isn't in it as it is may have
+// been changed by -c or -s, so it is looked up again in lower case, then
+// without each of its `specials` in turn, and the first match wins.
+pub fn decode<W: WordList + ?Sized>(
+    word_list: &W,
+    phrase: &str,
+    separator: Option<char>,
+    specials: &[char],
+) -> Vec<Decoded> {
+    let mut indices = HashMap::<&str, usize>::new();
+    for index in 0..word_list.len() {
+        if let Some(word) = word_list.word(index) {
+            indices.insert(word, index);
+        }
+    }
+
+    let tokens: Vec<&str> = match separator {
+        Some(separator) => phrase.split(separator).filter(|t| !t.is_empty()).collect(),
+        None => phrase.split_whitespace().collect(),
+    };
+    let decode_all = |tokens: &[&str]| -> Vec<Decoded> {
+        tokens
+            .iter()
+            .map(|&token| Decoded {
+                token: token.into(),
+                found: find(word_list, &indices, specials, token),
+            })
+            .collect()
+    };
+    let mut decoded = Vec::new();
+    for token in tokens {
+        let whole = decode_all(&[token]);
+        if separator.is_some() || whole[0].found != Found::NotInList {
+            decoded.extend(whole);
+            continue;
+        }
+        // --case camel writes the words one after the other, each with a
+        // capital to tell where it starts
+        let split = decode_all(&camel_words(token));
+        let words = |d: &[Decoded]| d.iter().filter(|d| d.found != Found::NotInList).count();
+        if words(&split) > words(&whole) {
+            decoded.extend(split);
+        } else {
+            decoded.extend(whole);
+        }
+    }
+
+    decoded
+}
+
+// Splits `token` before each upper case letter that doesn't follow another
+pub(crate) fn camel_words(token: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut after_upper = true;
+    for (idx, ch) in token.char_indices() {
+        if ch.is_uppercase() && !after_upper {
+            words.push(&token[start..idx]);
+            start = idx;
+        }
+        after_upper = ch.is_uppercase();
+    }
+    words.push(&token[start..]);
+
+    words
+}
+
+fn find<W: WordList + ?Sized>(
+    word_list: &W,
+    indices: &HashMap<&str, usize>,
+    specials: &[char],
+    token: &str,
+) -> Found {
+    let found = |word: &str, specials: &[char]| {
+        let index = indices.get(word)?;
+        Some(Found::Word {
+            word: word.into(),
+            code: word_roll(word_list, *index),
+            capital: false,
+            specials: specials.into(),
+        })
+    };
+    let found_either_case = |word: &str, specials: &[char]| {
+        found(word, specials).or_else(|| {
+            let lower = word.to_lowercase();
+            (lower != word)
+                .then(|| found(&lower, specials))
+                .flatten()
+                .map(|mut found| {
+                    if let Found::Word { capital, .. } = &mut found {
+                        *capital = true;
+                    }
+                    found
+                })
+        })
+    };
+
+    if let Some(found) = found_either_case(token, &[]) {
+        return found;
+    }
+    if let Some(code) = special_char_code(specials, token) {
+        return Found::SpecialChar { code };
+    }
+    let graphemes: Vec<&str> = token.graphemes(true).collect();
+    for (idx, grapheme) in graphemes.iter().enumerate() {
+        let Some(ch) = special_char(specials, grapheme) else {
+            continue;
+        };
+        let rest = [&graphemes[..idx], &graphemes[idx + 1..]].concat().concat();
+        if let Some(found) = found_either_case(&rest, &[ch]) {
+            return found;
+        }
+    }
+    // --specials may have put more than one into the word
+    let (removed, rest): (Vec<&str>, Vec<&str>) = graphemes
+        .iter()
+        .partition(|grapheme| special_char(specials, grapheme).is_some());
+    if removed.len() > 1 {
+        let removed: Vec<char> = removed.concat().chars().collect();
+        if let Some(found) = found_either_case(&rest.concat(), &removed) {
+            return found;
+        }
+    }
+
+    Found::NotInList
+}
+
+// The special character a grapheme is, if it is one of `specials`
+fn special_char(specials: &[char], grapheme: &str) -> Option<char> {
+    let mut chars = grapheme.chars();
+    let ch = chars.next()?;
+    (chars.next().is_none() && specials.contains(&ch)).then_some(ch)
+}
+
+// The dice that pick `token` from `specials`, if it is one of them
+fn special_char_code(specials: &[char], token: &str) -> Option<String> {
+    let ch = special_char(specials, token)?;
+    let value = specials.iter().position(|c| *c == ch)?;
+
+    Some(choice_roll(value, specials.len()))
+}
+
+// One line per word, with its dice code and what was undone to find it,
+// then the codes on their own as a compact backup.
+pub fn format_decoded(decoded: &[Decoded]) -> String {
+    let mut text = String::new();
+    let mut codes = Vec::<&str>::new();
+    for d in decoded {
+        match &d.found {
+            Found::Word {
+                word,
+                code,
+                capital,
+                specials,
+            } => {
+                text += &format!("{code:<5} {word}");
+                let mut undone = Vec::<String>::new();
+                if *capital {
+                    undone.push("capital letter".into());
+                }
+                for ch in specials {
+                    undone.push(format!("special character {ch}"));
+                }
+                if !undone.is_empty() {
+                    text += &format!(" (from {}, removed {})", d.token, undone.join(" and "));
+                }
+                codes.push(code);
+            }
+            Found::SpecialChar { code } => {
+                text += &format!("{code:<5} special character {}", d.token);
+                codes.push(code);
+            }
+            Found::NotInList => {
+                text += &format!("?     {} is not in the word list", d.token);
+                codes.push("?");
+            }
+        }
+        text += "\n";
+    }
+    text += &format!("codes: {}\n", codes.join(" "));
+
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{EFF, ORIGINAL};
+
+    fn dice() -> Vec<char> {
+        special_char_set(None)
+    }
+
+    fn word(word: &str, code: &str, capital: bool, special: Option<char>) -> Found {
+        Found::Word {
+            word: word.into(),
+            code: code.into(),
+            capital,
+            specials: special.into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn decode_plain_phrase() {
+        let decoded = decode(&ORIGINAL, "a levi norm", None, &dice());
+        let found: Vec<Found> = decoded.into_iter().map(|d| d.found).collect();
+
+        assert_eq!(
+            found,
+            [
+                word("a", "11111", false, None),
+                word("levi", "36355", false, None),
+                word("norm", "43634", false, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_undoes_capital_and_special_char() {
+        let decoded = decode(&EFF, "pAs$sing Zoom abacus%", None, &dice());
+        let found: Vec<Found> = decoded.into_iter().map(|d| d.found).collect();
+
+        assert_eq!(
+            found,
+            [
+                word("passing", "43634", true, Some('$')),
+                word("zoom", "66666", true, None),
+                word("abacus", "11111", false, Some('%')),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_undoes_several_special_chars() {
+        let decoded = decode(&EFF, "$ab%acus zoom", None, &dice());
+
+        assert_eq!(
+            decoded[0].found,
+            Found::Word {
+                word: "abacus".into(),
+                code: "11111".into(),
+                capital: false,
+                specials: vec!['$', '%'],
+            }
+        );
+    }
+
+    #[test]
+    fn decode_splits_camel_case() {
+        let decoded = decode(&EFF, "ZoomAba$cusPassing", None, &dice());
+        let found: Vec<Found> = decoded.into_iter().map(|d| d.found).collect();
+
+        assert_eq!(
+            found,
+            [
+                word("zoom", "66666", true, None),
+                word("abacus", "11111", true, Some('$')),
+                word("passing", "43634", true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_prefers_words_of_the_list() {
+        // "a&p" is a word of the original list, not "ap" with a special char
+        let decoded = decode(&ORIGINAL, "a&p", None, &dice());
+
+        assert_eq!(decoded[0].found, word("a&p", "11112", false, None));
+    }
+
+    #[test]
+    fn decode_special_char_token() {
+        let decoded = decode(&EFF, "zoom-(-abacus", Some('-'), &dice());
+
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[1].found, Found::SpecialChar { code: "23".into() });
+    }
+
+    #[test]
+    fn decode_special_chars_of_another_set() {
+        // '~' is the 4th of the 4 url-safe characters, '$' isn't one of them
+        let url_safe = special_char_set(Some("url-safe"));
+        let decoded = decode(&EFF, "zoom ~ aba~cus pas$sing", None, &url_safe);
+
+        assert_eq!(decoded[1].found, Found::SpecialChar { code: "4".into() });
+        assert_eq!(decoded[2].found, word("abacus", "11111", false, Some('~')));
+        assert_eq!(decoded[3].found, Found::NotInList);
+    }
+
+    #[test]
+    fn decode_leaves_the_separator_out_of_the_special_chars() {
+        // '!' is all that is left of the set once '-' is taken out
+        let cli_args = Args::parse_from(["sppg", "-e", "-S", "-", "--special-chars=-!"]);
+        let decoded = decode_phrase(&cli_args, "abacus-!-abdomen").unwrap();
+
+        assert_eq!(specials_of(&cli_args), ['!']);
+        assert_eq!(decoded[1].found, Found::SpecialChar { code: "1".into() });
+    }
+
+    #[test]
+    fn decode_flags_unknown_words() {
+        let decoded = decode(&EFF, "zoom xyzzy", None, &dice());
+
+        assert_eq!(decoded[1].token, "xyzzy");
+        assert_eq!(decoded[1].found, Found::NotInList);
+    }
+
+    #[test]
+    fn decode_list_without_dice() {
+        let list: Vec<String> = ["one", "two", "three"].map(String::from).into();
+        let decoded = decode(&list, "Three one", None, &dice());
+
+        assert_eq!(decoded[0].found, word("three", "#3", true, None));
+        assert_eq!(decoded[1].found, word("one", "#1", false, None));
+    }
+
+    #[test]
+    fn format_decoded_phrase() {
+        let decoded = decode(&EFF, "pAs$sing ( xyzzy", None, &dice());
+
+        assert_eq!(
+            format_decoded(&decoded),
+            "43634 passing (from pAs$sing, removed capital letter and special character $)\n\
+             23    special character (\n\
+             ?     xyzzy is not in the word list\n\
+             codes: 43634 23 ?\n"
+        );
+    }
+}
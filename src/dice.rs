@@ -0,0 +1,386 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    capital_char_positions, capital_label, capitalize, choice_roll, choose_word_list,
+    cli::Args,
+    insert_label, insert_special_chars, log2_choose,
+    passphrase::PassPhrase,
+    placement_entropy,
+    policy::{rejection, some_are_kept},
+    position_label,
+    recipe::Recipe,
+    rng_from_args, special_char_gaps, style_case, word_count, word_roll, Error, WordList,
+};
+
+#[derive(Debug)]
+pub enum DiceError {
+    Io(io::Error),
+    // The input ended before every roll was read
+    EndOfInput,
+    // The list doesn't have 6^n words, so dice can't pick from it
+    NoDice {
+        list: String,
+        len: usize,
+    },
+    InvalidDie {
+        roll: String,
+    },
+    Length {
+        roll: String,
+        found: usize,
+        expected: usize,
+    },
+    NotInList {
+        roll: String,
+    },
+    // The word doesn't fit in what is left of the maximum length
+    TooLong {
+        roll: String,
+        limit: usize,
+    },
+    // The word has characters the rules don't allow
+    Disallowed {
+        roll: String,
+    },
+    // The legacy policy or the rules threw the rolled phrase away
+    Rejected,
+}
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceError::Io(e) => write!(f, "unable to read the rolls: {e}"),
+            DiceError::EndOfInput => {
+                write!(f, "the rolls ran out before the passphrase was complete")
+            }
+            DiceError::NoDice { list, len } => write!(
+                f,
+                "word list '{list}' has {len} words, which dice can't pick from; use a list of 6, 36, 216, 1296 or 7776 words"
+            ),
+            DiceError::InvalidDie { roll } => {
+                write!(f, "invalid roll '{roll}': every die must be 1 to 6")
+            }
+            DiceError::Length {
+                roll,
+                found,
+                expected,
+            } => write!(f, "roll '{roll}' has {found} dice, expected {expected}"),
+            DiceError::NotInList { roll } => write!(f, "roll '{roll}' is not in the word list"),
+            DiceError::TooLong { roll, limit } => write!(
+                f,
+                "the word of roll '{roll}' is longer than the {limit} characters left for it"
+            ),
+            DiceError::Disallowed { roll } => write!(
+                f,
+                "the word of roll '{roll}' has characters the rules don't allow"
+            ),
+            DiceError::Rejected => write!(
+                f,
+                "the policy or the rules reject this passphrase; roll a new one or use more words"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DiceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DiceError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DiceError {
+    fn from(e: io::Error) -> Self {
+        DiceError::Io(e)
+    }
+}
+
+// Rolls of physical dice, read as groups of digits separated by white
+// space. With a prompt the user is asked for each roll and may correct a
+// bad one; without, as when the rolls are piped in, a bad roll is an error.
+pub struct Rolls<I, O> {
+    input: I,
+    prompt: Option<O>,
+    pending: VecDeque<String>,
+}
+
+impl<I: BufRead, O: Write> Rolls<I, O> {
+    pub fn new(input: I, prompt: Option<O>) -> Self {
+        Self {
+            input,
+            prompt,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn next_group(&mut self, what: &str, dice: usize) -> Result<String, DiceError> {
+        while self.pending.is_empty() {
+            if let Some(prompt) = &mut self.prompt {
+                write!(prompt, "{what}, roll {dice} {}: ", plural(dice))?;
+                prompt.flush()?;
+            }
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Err(DiceError::EndOfInput);
+            }
+            self.pending
+                .extend(line.split_whitespace().map(String::from));
+        }
+
+        Ok(self.pending.pop_front().unwrap())
+    }
+
+    // Reads a roll of `dice` dice and passes it to `check`, asking for it
+    // again if there is a prompt to ask with.
+    fn read<T>(
+        &mut self,
+        what: &str,
+        dice: usize,
+        mut check: impl FnMut(&str, usize) -> Result<T, DiceError>,
+    ) -> Result<T, DiceError> {
+        loop {
+            let roll = self.next_group(what, dice)?;
+            let result = parse_roll(&roll, dice).and_then(|value| check(&roll, value));
+            match (result, &mut self.prompt) {
+                (Err(e), Some(prompt)) => {
+                    writeln!(prompt, "error: {e}")?;
+                    // The rest of the line may have been meant for the
+                    // roll that was rejected
+                    self.pending.clear();
+                }
+                (result, _) => return result,
+            }
+        }
+    }
+
+    // Picks one of `count` choices. As 6^n is rarely a multiple of `count`
+    // the rolls past the last choice are rolled again, the same way Uniform
+    // rejects samples, so that every choice is equally likely.
+    fn choose(&mut self, what: &str, count: usize) -> Result<usize, DiceError> {
+        let dice = dice_for(count);
+        loop {
+            let value = self.read(what, dice, |_, value| Ok(value))?;
+            if value < count {
+                return Ok(value);
+            }
+            if let Some(prompt) = &mut self.prompt {
+                writeln!(
+                    prompt,
+                    "that is past the last of {count} choices, roll again"
+                )?;
+            }
+        }
+    }
+}
+
+fn plural(dice: usize) -> &'static str {
+    if dice == 1 {
+        "die"
+    } else {
+        "dice"
+    }
+}
+
+// The fewest dice with at least `count` outcomes
+pub(crate) fn dice_for(count: usize) -> usize {
+    let mut dice = 1;
+    let mut outcomes = 6;
+    while outcomes < count {
+        outcomes *= 6;
+        dice += 1;
+    }
+
+    dice
+}
+
+// Reads a roll of `dice` dice, e.g. "36355", as a number from 0 to
+// 6^dice - 1 in the order of the word lists.
+pub fn parse_roll(roll: &str, dice: usize) -> Result<usize, DiceError> {
+    if roll.bytes().any(|b| !(b'1'..=b'6').contains(&b)) {
+        return Err(DiceError::InvalidDie { roll: roll.into() });
+    }
+    if roll.len() != dice {
+        return Err(DiceError::Length {
+            roll: roll.into(),
+            found: roll.len(),
+            expected: dice,
+        });
+    }
+
+    Ok(roll
+        .bytes()
+        .fold(0, |value, b| value * 6 + (b - b'1') as usize))
+}
+
+// Makes a passphrase from the list chosen by `cli_args` with rolls of
+// physical dice in place of the random number generator.
+pub fn dice_passphrase<I, O>(cli_args: &Args, rolls: &mut Rolls<I, O>) -> Result<PassPhrase, Error>
+where
+    I: BufRead,
+    O: Write,
+{
+    let word_list = choose_word_list(cli_args)?;
+
+    passphrase_from_rolls(cli_args, word_list.as_ref(), rolls)
+}
+
+pub fn passphrase_from_rolls<W, I, O>(
+    cli_args: &Args,
+    word_list: &W,
+    rolls: &mut Rolls<I, O>,
+) -> Result<PassPhrase, Error>
+where
+    W: WordList + ?Sized,
+    I: BufRead,
+    O: Write,
+{
+    let recipe = Recipe::from_args(cli_args)?;
+    let words = recipe.words(word_list)?;
+    let dice = words.dice_count().ok_or_else(|| DiceError::NoDice {
+        list: words.name().into(),
+        len: words.len(),
+    })?;
+    // The generator is only used to estimate what the policy and rules cost
+    let mut rng = rng_from_args(cli_args);
+    let word_count = word_count(cli_args, &recipe, &words, rng.as_mut())?;
+    recipe.check_length(&words, word_count)?;
+
+    // The rolls are kept whether or not they are shown, as they are what
+    // the phrase was made from
+    let mut passphrase = recipe.passphrase();
+    passphrase.keep_rolls();
+    // A word the rules don't allow or too long for what is left of the
+    // maximum length is rolled again, which leaves each of the words that
+    // may be drawn equally likely
+    let mut room = recipe.room(&words, word_count);
+    for i in 0..word_count {
+        let what = format!("word {} of {word_count}", i + 1);
+        let limit = room.map(|room| recipe.word_limit(&words, room, word_count - i - 1));
+        let (index, word) = rolls.read(&what, dice, |roll, value| {
+            let index = words
+                .index_of_roll(value)
+                .ok_or_else(|| DiceError::Disallowed { roll: roll.into() })?;
+            let word = words
+                .word(index)
+                .map(|word| recipe.render(word))
+                .ok_or_else(|| DiceError::NotInList { roll: roll.into() })?;
+            match limit {
+                Some(limit) if word.chars().count() > limit => Err(DiceError::TooLong {
+                    roll: roll.into(),
+                    limit,
+                }),
+                _ => Ok((index, word)),
+            }
+        })?;
+        let bits = match limit {
+            Some(limit) => (words.fitting(limit) as f64).log2(),
+            None => words.entropy_per_word(),
+        };
+        if let Some(room) = &mut room {
+            *room -= word.chars().count();
+        }
+        passphrase.push(&word);
+        passphrase.add_entropy(bits);
+        passphrase.add_roll(word_roll(&words, index), &word);
+    }
+
+    style_case(&mut passphrase, recipe.case, |what, count| {
+        rolls.choose(what, count)
+    })?;
+
+    if recipe.capitals > 0 {
+        let count = capital_char_positions(&passphrase);
+        for left in (count.saturating_sub(recipe.capitals) + 1..=count).rev() {
+            let n = rolls.choose(&format!("capital letter (1 of {left})"), left)?;
+            capitalize(&mut passphrase, n);
+            passphrase.add_roll(choice_roll(n, left), &capital_label(n, left));
+        }
+        passphrase.add_entropy(log2_choose(count, recipe.capitals.min(count)));
+    }
+
+    // The 36 special characters are a roll of two dice, which is never
+    // past the last of them. The gaps are those of the phrase before any
+    // character was added, less the ones already taken.
+    let gaps = special_char_gaps(&passphrase, recipe.placement);
+    let mut free: Vec<usize> = (0..gaps).collect();
+    let mut placed = Vec::new();
+    let mut counts = Vec::new();
+    for insert in &recipe.inserts {
+        let mut count = 0;
+        for _ in 0..insert.count {
+            let index = rolls.choose(insert.what, insert.chars.len())?;
+            let ch = insert.chars[index];
+            passphrase.add_roll(
+                choice_roll(index, insert.chars.len()),
+                &insert_label(insert, ch),
+            );
+            let left = free.len();
+            if left > 0 {
+                let what = format!("position of the {} (1 of {left})", insert.what);
+                let n = rolls.choose(&what, left)?;
+                placed.push((free.remove(n), ch));
+                count += 1;
+                passphrase.add_entropy(insert.entropy());
+                passphrase.add_roll(choice_roll(n, left), &position_label(insert, n, left));
+            }
+        }
+        counts.push(count);
+    }
+    passphrase.add_entropy(placement_entropy(gaps, &counts));
+    insert_special_chars(&mut passphrase, recipe.placement, &placed);
+
+    if recipe.may_reject() {
+        if !recipe.accepts(&passphrase) {
+            return Err(DiceError::Rejected.into());
+        }
+        let rejection = rejection(&recipe, &words, word_count, rng.as_mut());
+        some_are_kept(&recipe, &words, word_count, &rejection, 1, rng.as_mut())?;
+        passphrase.add_entropy(-rejection.entropy_loss());
+    }
+
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_rolls() {
+        assert_eq!(parse_roll("11111", 5).unwrap(), 0);
+        assert_eq!(parse_roll("11112", 5).unwrap(), 1);
+        assert_eq!(parse_roll("66666", 5).unwrap(), 7775);
+        assert_eq!(parse_roll("21", 2).unwrap(), 6);
+    }
+
+    #[test]
+    fn parse_roll_errors() {
+        assert_eq!(
+            parse_roll("12370", 5).unwrap_err().to_string(),
+            "invalid roll '12370': every die must be 1 to 6"
+        );
+        assert_eq!(
+            parse_roll("1234", 5).unwrap_err().to_string(),
+            "roll '1234' has 4 dice, expected 5"
+        );
+        assert_eq!(
+            parse_roll("123456", 5).unwrap_err().to_string(),
+            "roll '123456' has 6 dice, expected 5"
+        );
+    }
+
+    #[test]
+    fn dice_for_counts() {
+        assert_eq!(dice_for(1), 1);
+        assert_eq!(dice_for(6), 1);
+        assert_eq!(dice_for(7), 2);
+        assert_eq!(dice_for(36), 2);
+        assert_eq!(dice_for(37), 3);
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::{crack::ConfigError, dice::DiceError, rules::RulesError, WordListError};
+
+#[derive(Debug)]
+pub enum Error {
+    WordList(WordListError),
+    Dice(DiceError),
+    Config(ConfigError),
+    Rules(RulesError),
+    // Every word in the list is the same, so no number of words is enough
+    NoEntropy,
+    // No word of the list has a letter -c could capitalize
+    NoCapitals {
+        list: String,
+    },
+    // Even the shortest words make phrases longer than --max-length
+    MaxLength {
+        max: usize,
+        word_count: usize,
+        shortest: usize,
+    },
+    // Every character of one kind added to phrases is also of another
+    // kind, which keeps it, so none are left to add
+    SharedChars {
+        what: &'static str,
+        other: &'static str,
+    },
+    // The policy rejected too many phrases to fill the requested number
+    TooManyAttempts {
+        wanted: usize,
+        found: usize,
+        attempts: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::WordList(e) => write!(f, "{e}"),
+            Error::Dice(e) => write!(f, "{e}"),
+            Error::Config(e) => write!(f, "{e}"),
+            Error::Rules(e) => write!(f, "{e}"),
+            Error::NoEntropy => write!(f, "the words in the list carry no entropy"),
+            Error::NoCapitals { list } => write!(
+                f,
+                "-c adds a capital letter, but no word of list '{list}' has a letter with an upper case"
+            ),
+            Error::MaxLength {
+                max,
+                word_count,
+                shortest,
+            } => write!(
+                f,
+                "--max-length {max} is too short: the shortest phrase of {word_count} words is {shortest} characters"
+            ),
+            Error::SharedChars { what, other } => write!(
+                f,
+                "every {what} to add is also a {other}, and the two have to be told apart; \
+                 use {what}s that aren't {other}s"
+            ),
+            Error::TooManyAttempts {
+                wanted,
+                found,
+                attempts,
+            } => write!(
+                f,
+                "unable to derive a secure enough passphrase: found {found} of {wanted} in {attempts} attempts; \
+                 try increasing the word count or adding quality (--quality)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::WordList(e) => Some(e),
+            Error::Dice(e) => Some(e),
+            Error::Config(e) => Some(e),
+            Error::Rules(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<WordListError> for Error {
+    fn from(e: WordListError) -> Self {
+        Error::WordList(e)
+    }
+}
+
+impl From<DiceError> for Error {
+    fn from(e: DiceError) -> Self {
+        Error::Dice(e)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Error::Config(e)
+    }
+}
+
+impl From<RulesError> for Error {
+    fn from(e: RulesError) -> Self {
+        Error::Rules(e)
+    }
+}
@@ -1,165 +1,745 @@
+use std::{cmp::Reverse, collections::HashMap, convert::Infallible};
+
 use rand::{
     distributions::{Distribution, Uniform},
-    rngs::ThreadRng,
+    CryptoRng, RngCore,
 };
-use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
 
+pub mod check;
 pub mod cli;
+pub mod crack;
+pub mod decode;
+pub mod dice;
+mod error;
 pub mod passphrase;
-mod wordlist;
+pub mod policy;
+pub mod recipe;
+pub mod rng;
+pub mod rules;
+pub mod wordlist;
 use cli::Args;
+use crack::{format_crack_times, load_models, AttackerModel};
+use dice::dice_for;
+pub use error::Error;
 use passphrase::PassPhrase;
-use wordlist::{eff::EFF_WORDLIST, original::ORIGINAL_WORDLIST, special_char::SPECIAL_CHARS};
+use policy::{minimum_entropy, rejection, some_are_kept, words_for_entropy, Policy};
+use recipe::{Insert, Recipe, Words};
+pub use rng::{rng_for, seeded_rng, RngSource, SecureRng};
+use rules::RulesError;
+use wordlist::{dice_count_for, special_char::SPECIAL_CHARS};
+pub use wordlist::{
+    file::{load_word_list, parse_word_list, FileWordList, WordListError},
+    find_builtin_list, sample_index, EmbeddedList, WordList, BUILTIN_LISTS, EFF, ORIGINAL,
+};
 
-#[macro_use]
-extern crate lazy_static;
+pub fn choose_word_list(cli_args: &Args) -> Result<Box<dyn WordList>, WordListError> {
+    if let Some(path) = &cli_args.wordlist {
+        return Ok(Box::new(load_word_list(path)?));
+    }
+    if let Some(name) = &cli_args.list {
+        let list = find_builtin_list(name).ok_or_else(|| WordListError::Unknown(name.clone()))?;
+        return Ok(Box::new(list));
+    }
+    if cli_args.eff {
+        return Ok(Box::new(&EFF));
+    }
 
-lazy_static! {
-    static ref LIST_ORIG: HashMap<&'static str, &'static str> =
-        ORIGINAL_WORDLIST.into_iter().collect();
+    Ok(Box::new(&ORIGINAL))
 }
-lazy_static! {
-    static ref LIST_EFF: HashMap<&'static str, &'static str> = EFF_WORDLIST.into_iter().collect();
+
+// With the legacy policy or --rules a phrase may be rejected, so each one
+// that is asked for gets this many attempts before generation gives up
+const ATTEMPTS_PER_PASSPHRASE: usize = 100;
+
+pub fn iterate(cli_args: &Args) -> Result<Vec<PassPhrase>, Error> {
+    let word_list = choose_word_list(cli_args)?;
+
+    generate(cli_args, word_list.as_ref())
 }
 
-pub fn choose_word_list(cli_args: &Args) -> &'static HashMap<&'static str, &'static str> {
-    if cli_args.eff {
-        return &LIST_EFF;
-    }
+// Generates with the source of randomness chosen by --rng or --seed
+pub fn generate<W: WordList + ?Sized>(
+    cli_args: &Args,
+    word_list: &W,
+) -> Result<Vec<PassPhrase>, Error> {
+    let mut rng = rng_from_args(cli_args);
 
-    &LIST_ORIG
+    generate_with_rng(cli_args, word_list, rng.as_mut())
 }
 
-pub fn iterate(cli_args: &Args) -> Vec<PassPhrase> {
-    let separator = cli_args.separator;
-    let word_count = cli_args.word_count;
-    let iterations = cli_args.num_of_pass;
-    let diceware_map = choose_word_list(cli_args);
+pub fn generate_with_rng<W, R>(
+    cli_args: &Args,
+    word_list: &W,
+    rng: &mut R,
+) -> Result<Vec<PassPhrase>, Error>
+where
+    W: WordList + ?Sized,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    let iterations = cli_args.num_of_pass as usize;
+    let recipe = Recipe::from_args(cli_args)?;
+    let words = recipe.words(word_list)?;
+    let word_count = word_count(cli_args, &recipe, &words, rng)?;
+    recipe.check_length(&words, word_count)?;
+    // Only the phrases the policy and rules keep can be printed, so each of
+    // them is more likely than its own choices say by the share that is
+    // thrown away.
+    let rejection = rejection(&recipe, &words, word_count, rng);
+    some_are_kept(&recipe, &words, word_count, &rejection, iterations, rng)?;
+    let entropy_loss = rejection.entropy_loss();
+    let max_attempts = iterations * ATTEMPTS_PER_PASSPHRASE;
     let mut list = Vec::<PassPhrase>::new();
-    for _ in 0..iterations {
-        let mut passphrase = PassPhrase::new(separator);
-        for _ in 0..word_count {
-            let lookup = roll_dice_5_times();
-            let word = lookup_word(&lookup, diceware_map);
-            passphrase.push(word);
+    let mut attempts = 0;
+    // How many phrases each rule threw away, to name the one to blame
+    let mut broken = HashMap::<String, usize>::new();
+    while list.len() < iterations {
+        if attempts == max_attempts {
+            if let Some((rule, count)) = broken.into_iter().max_by_key(|(_, count)| *count) {
+                if count * 2 > attempts {
+                    return Err(Error::Rules(RulesError::Unsatisfiable {
+                        rule,
+                        reason: format!(
+                            "it threw away {count} of {attempts} phrases, leaving {} of the {iterations} asked for",
+                            list.len()
+                        ),
+                    }));
+                }
+            }
+            return Err(Error::TooManyAttempts {
+                wanted: iterations,
+                found: list.len(),
+                attempts,
+            });
         }
+        attempts += 1;
 
-        // This needs to be done before adding a special character so as to
-        // not run the risk of attempting to convert a special character
-        // to uppercase.
-        if cli_args.use_capital_char {
-            add_capital_char(&mut passphrase);
+        let mut passphrase = build_passphrase(&recipe, &words, word_count, rng);
+        if !recipe.accepts(&passphrase) {
+            let phrase = passphrase.to_string();
+            if let Some(rule) = recipe.rules.as_ref().and_then(|r| r.broken_by(&phrase)) {
+                *broken.entry(rule).or_default() += 1;
+            }
+            continue;
         }
+        passphrase.add_entropy(-entropy_loss);
+        list.push(passphrase)
+    }
+
+    Ok(list)
+}
 
-        if cli_args.use_special_char {
-            let ch = roll_for_special_char();
-            let _ = add_special_char(&mut passphrase, ch).clone();
+pub(crate) fn rng_from_args(cli_args: &Args) -> Box<dyn SecureRng> {
+    match cli_args.seed {
+        Some(seed) => Box::new(seeded_rng(seed)),
+        None => rng_for(cli_args.rng),
+    }
+}
+
+// The number of words in each phrase, either as given or as many as
+// --min-entropy needs
+pub fn word_count<W, R>(
+    cli_args: &Args,
+    recipe: &Recipe,
+    words: &Words<W>,
+    rng: &mut R,
+) -> Result<usize, Error>
+where
+    W: WordList + ?Sized,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    match cli_args.min_entropy {
+        Some(target) => words_for_entropy(recipe, words, target as f64, rng),
+        None => Ok(cli_args.word_count as usize),
+    }
+}
+
+// Makes one phrase from `recipe`, before any policy or rule has judged it.
+pub(crate) fn build_passphrase<W, R>(
+    recipe: &Recipe,
+    words: &Words<W>,
+    word_count: usize,
+    rng: &mut R,
+) -> PassPhrase
+where
+    W: WordList + ?Sized,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    let mut passphrase = recipe.passphrase();
+    // Under a maximum length each word is drawn from those that leave room
+    // for the rest, so that no phrase has to be thrown away for its length
+    let mut room = recipe.room(words, word_count);
+    for words_left in (0..word_count).rev() {
+        let (index, bits) = match room {
+            Some(room) => {
+                let count = words.fitting(recipe.word_limit(words, room, words_left));
+                let n = roll_choice(count, rng);
+                (words.nth_fitting(n), (count as f64).log2())
+            }
+            None => (sample_index(words, rng), words.entropy_per_word()),
+        };
+        let word = recipe.render(lookup_word(index, words));
+        if let Some(room) = &mut room {
+            *room -= word.chars().count();
         }
+        passphrase.push(&word);
+        passphrase.add_entropy(bits);
+        if passphrase.keeps_rolls() {
+            passphrase.add_roll(word_roll(words, index), &word);
+        }
+    }
 
-        if passphrase.is_insecure() {
-            continue;
+    style_case(&mut passphrase, recipe.case, |_, count| {
+        Ok::<_, Infallible>(roll_choice(count, rng))
+    })
+    .unwrap_or_else(|never| match never {});
+
+    // This needs to be done before adding a special character so as to
+    // not run the risk of attempting to convert a special character
+    // to uppercase.
+    if recipe.capitals > 0 {
+        let count = capital_char_positions(&passphrase);
+        for left in (count.saturating_sub(recipe.capitals) + 1..=count).rev() {
+            let n = roll_choice(left, rng);
+            capitalize(&mut passphrase, n);
+            if passphrase.keeps_rolls() {
+                passphrase.add_roll(choice_roll(n, left), &capital_label(n, left));
+            }
         }
-        list.push(passphrase)
+        passphrase.add_entropy(log2_choose(count, recipe.capitals.min(count)));
     }
-    if list.is_empty() && iterations > 0 {
-        eprintln!("error: unable to derive a secure enough passphrase");
-        eprintln!("error: try increasing the word count or adding quality (--quality).");
+
+    if recipe.inserts.is_empty() {
+        return passphrase;
     }
 
-    list
+    // Each character goes into a gap of its own, picked from those of the
+    // phrase before any was added that are still free
+    let gaps = special_char_gaps(&passphrase, recipe.placement);
+    let mut free: Vec<usize> = (0..gaps).collect();
+    let mut placed = Vec::new();
+    let mut counts = Vec::new();
+    for insert in &recipe.inserts {
+        let mut count = 0;
+        for _ in 0..insert.count {
+            let index = roll_insert(insert, rng);
+            let ch = insert.chars[index];
+            if passphrase.keeps_rolls() {
+                passphrase.add_roll(
+                    choice_roll(index, insert.chars.len()),
+                    &insert_label(insert, ch),
+                );
+            }
+            let left = free.len();
+            if left > 0 {
+                let n = roll_choice(left, rng);
+                placed.push((free.remove(n), ch));
+                count += 1;
+                passphrase.add_entropy(insert.entropy());
+                if passphrase.keeps_rolls() {
+                    passphrase.add_roll(choice_roll(n, left), &position_label(insert, n, left));
+                }
+            }
+        }
+        counts.push(count);
+    }
+    passphrase.add_entropy(placement_entropy(gaps, &counts));
+    insert_special_chars(&mut passphrase, recipe.placement, &placed);
+
+    passphrase
+}
+
+// The dice code of the word at `index`, or its place in the list if the
+// list can't be used with dice
+pub(crate) fn word_roll<W: WordList + ?Sized>(word_list: &W, index: usize) -> String {
+    word_list
+        .dice_code(index)
+        .unwrap_or_else(|| format!("#{}", index + 1))
+}
+
+// The roll that picks the nth of `count` choices in `sppg dice`
+pub(crate) fn choice_roll(n: usize, count: usize) -> String {
+    wordlist::dice_code(n, dice_for(count))
+}
+
+pub(crate) fn capital_label(n: usize, count: usize) -> String {
+    format!("capital letter {} of {count}", n + 1)
+}
+
+pub(crate) fn insert_label(insert: &Insert, ch: char) -> String {
+    format!("{} {ch}", insert.what)
+}
+
+// log2 of the number of ways to choose `k` of `n` places
+pub(crate) fn log2_choose(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (0..k)
+        .map(|i| ((n - i) as f64 / (i + 1) as f64).log2())
+        .sum()
+}
+
+// The bits of entropy of where the characters of the inserts went, when
+// placed[i] of the ith went into distinct gaps of `gaps`. Swapping two
+// characters of one insert makes a phrase it could have made anyway, so
+// only which gaps each insert got counts. This is an upper bound: a
+// character put on either side of the same character, as '!' into the
+// original list's "!!", makes the same phrase from more than one gap.
+pub(crate) fn placement_entropy(gaps: usize, placed: &[usize]) -> f64 {
+    let mut left = gaps;
+    let mut bits = 0.0;
+    for count in placed {
+        bits += log2_choose(left, *count);
+        left = left.saturating_sub(*count);
+    }
+
+    bits
+}
+
+pub(crate) fn position_label(insert: &Insert, n: usize, count: usize) -> String {
+    format!("{} position {} of {count}", insert.what, n + 1)
 }
 
-pub fn add_special_char(pp: &mut PassPhrase, ch: char) -> &PassPhrase {
-    let dice = Uniform::from(0..pp.len() as u32);
-    let mut rng = rand::thread_rng();
-    let idx_word = roll_dice(&dice, &mut rng);
+// Phrases with fewer bits of entropy than this are weak enough to warn
+// about when --max-length is what makes them so
+pub const WEAK_ENTROPY: f64 = 60.0;
 
-    let len_word = pp[idx_word].len();
-    let mut idx_char: usize = 0;
-    if len_word > 1 {
-        let dice = Uniform::from(0..len_word as u32);
-        let mut rng = rand::thread_rng();
-        idx_char = roll_dice(&dice, &mut rng);
+// A warning if the maximum length leaves phrases with less than
+// WEAK_ENTROPY bits, or the --min-entropy asked for, and fewer than they
+// would have without it
+pub fn max_length_warning(cli_args: &Args) -> Result<Option<String>, Error> {
+    let recipe = Recipe::from_args(cli_args)?;
+    let Some(max) = recipe.max_length else {
+        return Ok(None);
+    };
+    let word_list = choose_word_list(cli_args)?;
+    let words = recipe.words(word_list.as_ref())?;
+    let mut rng = rng_from_args(cli_args);
+    let word_count = word_count(cli_args, &recipe, &words, rng.as_mut())?;
+    recipe.check_length(&words, word_count)?;
+    let capped = minimum_entropy(&recipe, &words, word_count);
+    let uncapped = minimum_entropy(
+        &Recipe {
+            max_length: None,
+            ..recipe.clone()
+        },
+        &words,
+        word_count,
+    );
+    let threshold = cli_args.min_entropy.map_or(WEAK_ENTROPY, f64::from);
+    if capped >= threshold || capped >= uncapped {
+        return Ok(None);
     }
 
-    if idx_char == 0 {
-        let w1 = &pp[idx_word][..len_word];
-        pp[idx_word] = format!("{ch}{w1}");
+    Ok(Some(format!(
+        "a maximum length of {max} leaves phrases of {word_count} words as little as {capped:.1} bits \
+         of entropy ({uncapped:.1} without it), less than {threshold}"
+    )))
+}
+
+// Describes what the chosen policy does to the phrases it is given, and
+// how many bits of entropy that costs.
+pub fn explain_policy(cli_args: &Args) -> Result<String, Error> {
+    let word_list = choose_word_list(cli_args)?;
+    let recipe = Recipe::from_args(cli_args)?;
+    let words = recipe.words(word_list.as_ref())?;
+    let mut rng = rng_from_args(cli_args);
+    let word_count = word_count(cli_args, &recipe, &words, rng.as_mut())?;
+    recipe.check_length(&words, word_count)?;
+    let bits = minimum_entropy(&recipe, &words, word_count);
+    let rejection = rejection(&recipe, &words, word_count, rng.as_mut());
+    let policy = match cli_args.policy {
+        Policy::Entropy => "entropy (every phrase is kept)",
+        Policy::Legacy => "legacy (phrases that look weak are thrown away)",
+    };
+
+    let mut text = format!("policy: {policy}\n");
+    if let Some(rules) = &cli_args.rules {
+        text += &format!("rules: {rules}\n");
+    }
+    text += &format!(
+        "word list: {} ({} words, {:.1} bits per word)\n",
+        words.name(),
+        words.len(),
+        words.entropy_per_word()
+    );
+    text += &format!("word count: {word_count}\n");
+    if let Some(max) = recipe.max_length {
+        text += &format!("maximum length: {max} characters\n");
+    }
+    text += &format!("entropy before the policy: at least {bits:.1} bits\n");
+    text += &format!("rejected: {:.2}% of phrases", rejection.rate * 100.0);
+    match rejection.samples {
+        Some(samples) => text += &format!(" (estimated from {samples} samples)\n"),
+        None => text += " (exact)\n",
+    }
+    if rejection.rate < 1.0 {
+        let loss = rejection.entropy_loss();
+        match rejection.samples {
+            Some(_) => {
+                text += &format!(
+                    "entropy lost: {loss:.2} ± {:.2} bits (95% confidence)\n",
+                    rejection.margin()
+                )
+            }
+            None => text += &format!("entropy lost: {loss:.2} bits\n"),
+        }
+        text += &format!(
+            "entropy after the policy: at least {:.1} bits\n",
+            bits - loss
+        );
     } else {
-        let word = &pp[idx_word];
-        let w1 = &word[0..=idx_char];
-        let w2 = &word[idx_char + 1..];
-        pp[idx_word] = format!("{w1}{ch}{w2}");
+        text += "entropy after the policy: none, every phrase is rejected\n";
     }
 
-    pp
+    Ok(text)
 }
 
-pub fn add_capital_char(pp: &mut PassPhrase) -> &PassPhrase {
-    let dice = Uniform::from(0..pp.len() as u32);
-    let mut rng = rand::thread_rng();
-    let idx_word = roll_dice(&dice, &mut rng);
+// Where add_special_char() may put the special character
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Placement {
+    // Before, inside or after one of the words
+    #[default]
+    Word,
+    // Between words, as a word of its own
+    Token,
+    // Any of the above
+    Any,
+}
 
-    let len_word = pp[idx_word].len();
-    let mut idx_char: usize = 0;
-    if len_word > 1 {
-        let dice = Uniform::from(0..len_word as u32);
-        let mut rng = rand::thread_rng();
-        idx_char = roll_dice(&dice, &mut rng);
+// How the letters of the words are cased, before any capital of -c
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Case {
+    // As they are in the word list
+    #[default]
+    Lower,
+    // The first letter of each word in upper case
+    Title,
+    // Title case, with no separator between the words
+    Camel,
+    // One word chosen at random in upper case
+    UpperWord,
+    // Each word in upper case or not, as a coin flip decides
+    Random,
+}
+
+impl Case {
+    // Whether every phrase gets an upper case letter, if its words have any
+    pub fn always_upper(self) -> bool {
+        matches!(self, Case::Title | Case::Camel | Case::UpperWord)
+    }
+}
+
+// Whether a word has letters with an upper case form
+pub(crate) fn has_case(word: &str) -> bool {
+    word.to_uppercase() != word
+}
+
+// `word` with its first grapheme in upper case, if it is a letter that
+// has one
+pub(crate) fn title_case(word: &str) -> String {
+    let mut graphemes = word.graphemes(true);
+    match graphemes.next() {
+        Some(first) if is_capitalizable(first) => first.to_uppercase() + graphemes.as_str(),
+        _ => word.into(),
     }
+}
 
-    let mut ch = String::from("");
-    let word = &pp[idx_word];
-    for (idx, c) in word.char_indices() {
-        if idx == idx_char {
-            // We convert to string because for some languages to_uppercase() may
-            // return more than one char.
-            if c.is_alphabetic() {
-                ch = c.to_uppercase().to_string();
+// Styles the case of the words of `pp` as `case` says, each random choice
+// being one of `count` made by `choose(what, count)`. Only words that have
+// a case are chosen from, since changing the others makes no difference.
+pub(crate) fn style_case<E, F>(pp: &mut PassPhrase, case: Case, mut choose: F) -> Result<(), E>
+where
+    F: FnMut(&str, usize) -> Result<usize, E>,
+{
+    let cased: Vec<usize> = match case {
+        Case::UpperWord | Case::Random => (0..pp.len()).filter(|i| has_case(&pp[*i])).collect(),
+        _ => Vec::new(),
+    };
+    match case {
+        Case::Lower => {}
+        Case::Title | Case::Camel => {
+            for i in 0..pp.len() {
+                pp[i] = title_case(&pp[i]);
             }
         }
+        Case::UpperWord if cased.is_empty() => {}
+        Case::UpperWord => {
+            let count = cased.len();
+            let n = choose(&format!("word in upper case (1 of {count})"), count)?;
+            let i = cased[n];
+            pp[i] = pp[i].to_uppercase();
+            pp.add_entropy((count as f64).log2());
+            if pp.keeps_rolls() {
+                pp.add_roll(
+                    choice_roll(n, count),
+                    &format!("word {} in upper case", i + 1),
+                );
+            }
+        }
+        Case::Random => {
+            for i in cased {
+                let n = choose(&format!("case of word {} (1 lower, 2 upper)", i + 1), 2)?;
+                let case = if n == 1 {
+                    pp[i] = pp[i].to_uppercase();
+                    "upper"
+                } else {
+                    "lower"
+                };
+                pp.add_entropy(1.0);
+                if pp.keeps_rolls() {
+                    pp.add_roll(choice_roll(n, 2), &format!("word {} in {case} case", i + 1));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Gap {
+    // Before the nth grapheme of a word, or after the word if n is its length
+    InWord(usize, usize),
+    // Before the nth word, or after the phrase if n is its length
+    Token(usize),
+}
+
+// Inserts `ch` into a gap chosen uniformly from every gap allowed by
+// `placement`, and returns the bits of entropy the choice of gap added. If
+// there is nowhere to put it the phrase is left as it is and None is
+// returned.
+pub fn add_special_char<R: RngCore + CryptoRng + ?Sized>(
+    pp: &mut PassPhrase,
+    ch: char,
+    placement: Placement,
+    rng: &mut R,
+) -> Option<f64> {
+    let count = special_char_gaps(pp, placement);
+    if count == 0 {
+        return None;
     }
-    let w1 = &word[0..idx_char];
-    let w2 = &word[idx_char + 1..];
+
+    insert_special_char(pp, ch, placement, roll_choice(count, rng));
+
+    Some((count as f64).log2())
+}
+
+// The number of gaps add_special_char() chooses from
+pub fn special_char_gaps(pp: &PassPhrase, placement: Placement) -> usize {
+    gaps(pp, placement).len()
+}
+
+// Inserts `ch` into the nth of the gaps counted by special_char_gaps()
+pub fn insert_special_char(pp: &mut PassPhrase, ch: char, placement: Placement, n: usize) {
+    insert_at_gap(pp, gaps(pp, placement)[n], ch);
+}
+
+// Inserts each character into the gap counted by special_char_gaps()
+// before any of them went in. Words are filled from their end, so that
+// the gaps before are where they were, and tokens go in last, from the end
+// of the phrase, as they move the words after them.
+pub(crate) fn insert_special_chars(
+    pp: &mut PassPhrase,
+    placement: Placement,
+    placed: &[(usize, char)],
+) {
+    let gaps = gaps(pp, placement);
+    let mut placed: Vec<(Gap, char)> = placed.iter().map(|(n, ch)| (gaps[*n], *ch)).collect();
+    placed.sort_by_key(|(gap, _)| {
+        Reverse(match *gap {
+            Gap::InWord(idx_word, idx_char) => (1, idx_word, idx_char),
+            Gap::Token(idx_word) => (0, idx_word, 0),
+        })
+    });
+    for (gap, ch) in placed {
+        insert_at_gap(pp, gap, ch);
+    }
+}
+
+fn insert_at_gap(pp: &mut PassPhrase, gap: Gap, ch: char) {
+    match gap {
+        Gap::InWord(idx_word, idx_char) => {
+            let graphemes: Vec<&str> = pp[idx_word].graphemes(true).collect();
+            let w1 = graphemes[..idx_char].concat();
+            let w2 = graphemes[idx_char..].concat();
+            pp[idx_word] = format!("{w1}{ch}{w2}");
+        }
+        Gap::Token(idx_word) => {
+            pp.insert(idx_word, &ch.to_string());
+        }
+    }
+}
+
+// Without a separator the end of a word is the start of the next, and a
+// token put between them is no different from either, so each of those
+// places is only counted once.
+fn gaps(pp: &PassPhrase, placement: Placement) -> Vec<Gap> {
+    let joined = pp.is_joined();
+    let mut gaps = Vec::<Gap>::new();
+    if placement != Placement::Token {
+        for idx_word in 0..pp.len() {
+            // Positions are counted in grapheme clusters, not bytes or chars,
+            // so that a letter is never separated from its accents.
+            let len_word = pp[idx_word].graphemes(true).count();
+            let first = usize::from(joined && idx_word > 0);
+            gaps.extend((first..=len_word).map(|idx_char| Gap::InWord(idx_word, idx_char)));
+        }
+    }
+    if placement == Placement::Token || (placement == Placement::Any && !joined) {
+        gaps.extend((0..=pp.len()).map(Gap::Token));
+    }
+
+    gaps
+}
+
+// Capitalizes one letter chosen uniformly from all of the letters in the
+// phrase that have an upper case form, and returns the bits of entropy the
+// choice added. If there are no such letters the phrase is left as it is
+// and None is returned.
+pub fn add_capital_char<R: RngCore + CryptoRng + ?Sized>(
+    pp: &mut PassPhrase,
+    rng: &mut R,
+) -> Option<f64> {
+    let count = capital_char_positions(pp);
+    if count == 0 {
+        return None;
+    }
+
+    capitalize(pp, roll_choice(count, rng));
+
+    Some((count as f64).log2())
+}
+
+// The number of letters add_capital_char() chooses from
+pub fn capital_char_positions(pp: &PassPhrase) -> usize {
+    capitalizable(pp).len()
+}
+
+// Capitalizes the nth of the letters counted by capital_char_positions()
+pub fn capitalize(pp: &mut PassPhrase, n: usize) {
+    let (idx_word, idx_char) = capitalizable(pp)[n];
+    let graphemes: Vec<&str> = pp[idx_word].graphemes(true).collect();
+    // The whole cluster is converted, and to a string, because for some
+    // letters to_uppercase() returns more than one char (e.g. 'ß' => "SS").
+    let ch = graphemes[idx_char].to_uppercase();
+    let w1 = graphemes[0..idx_char].concat();
+    let w2 = graphemes[idx_char + 1..].concat();
     pp[idx_word] = format!("{w1}{ch}{w2}");
+}
+
+fn capitalizable(pp: &PassPhrase) -> Vec<(usize, usize)> {
+    let mut positions = Vec::<(usize, usize)>::new();
+    for idx_word in 0..pp.len() {
+        for (idx_char, grapheme) in pp[idx_word].graphemes(true).enumerate() {
+            if is_capitalizable(grapheme) {
+                positions.push((idx_word, idx_char));
+            }
+        }
+    }
 
-    pp
+    positions
 }
 
-pub fn roll_for_special_char() -> char {
-    let dice = Uniform::from(0..6);
-    let mut rng = rand::thread_rng();
-    let x = roll_dice(&dice, &mut rng);
-    let y = roll_dice(&dice, &mut rng);
+// Digits, punctuation and letters without case (e.g. CJK) are skipped
+fn is_capitalizable(grapheme: &str) -> bool {
+    grapheme.starts_with(char::is_alphabetic) && grapheme.to_uppercase() != grapheme
+}
+
+pub fn special_char_entropy() -> f64 {
+    let count: usize = SPECIAL_CHARS.iter().map(|row| row.len()).sum();
+    (count as f64).log2()
+}
+
+pub fn roll_for_special_char<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> char {
+    let (x, y) = roll_special_char_cell(rng);
 
     SPECIAL_CHARS[x][y]
 }
 
-pub fn roll_dice_5_times() -> String {
+// The row and column of a special character, as two dice would pick them
+fn roll_special_char_cell<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> (usize, usize) {
+    let dice = Uniform::from(0..6);
+    let x = roll_dice(&dice, rng);
+    let y = roll_dice(&dice, rng);
+
+    (x, y)
+}
+
+// The index of a character of `insert`. A set that dice can pick from
+// exactly, such as the 36 of the special character table, is rolled one
+// die at a time, the way `sppg dice` reads it.
+fn roll_insert<R: RngCore + ?Sized>(insert: &Insert, rng: &mut R) -> usize {
+    let count = insert.chars.len();
+    match dice_count_for(count) {
+        Some(dice_count) => {
+            let dice = Uniform::from(0..6);
+            (0..dice_count).fold(0, |index, _| index * 6 + roll_dice(&dice, rng))
+        }
+        None => roll_choice(count, rng),
+    }
+}
+
+// One of `count` choices, each equally likely
+fn roll_choice<R: RngCore + ?Sized>(count: usize, rng: &mut R) -> usize {
+    let dice = Uniform::from(0..count as u32);
+    roll_dice(&dice, rng)
+}
+
+pub fn roll_dice_n_times<R: RngCore + CryptoRng + ?Sized>(count: usize, rng: &mut R) -> String {
     let mut lookup_number = String::new();
     let dice = Uniform::from(1..7);
-    let mut rng = rand::thread_rng();
-    for _ in 0..5 {
-        let number = roll_dice(&dice, &mut rng);
+    for _ in 0..count {
+        let number = roll_dice(&dice, rng);
         lookup_number.push(char::from_digit(number as u32, 10).unwrap());
     }
 
     lookup_number
 }
 
-fn roll_dice(dice: &Uniform<u32>, rng: &mut ThreadRng) -> usize {
+pub fn roll_word_index<W, R>(word_list: &W, rng: &mut R) -> usize
+where
+    W: WordList + ?Sized,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    sample_index(word_list, rng)
+}
+
+fn roll_dice<R: RngCore + ?Sized>(dice: &Uniform<u32>, rng: &mut R) -> usize {
     dice.sample(rng) as usize
 }
 
-pub fn lookup_word(
-    lookup: &str,
-    diceware_map: &'static HashMap<&'static str, &'static str>,
-) -> &'static str {
-    diceware_map.get(lookup).unwrap()
+pub fn lookup_word<W: WordList + ?Sized>(index: usize, word_list: &W) -> &str {
+    word_list.word(index).unwrap()
 }
 
-pub fn print_passphrases(list: &Vec<PassPhrase>) {
+// The attacker models for --show-crack-time and the check command
+pub fn load_attackers(cli_args: &Args) -> Result<Vec<AttackerModel>, Error> {
+    Ok(load_models(cli_args.attackers.as_deref())?)
+}
+
+pub fn print_passphrases(list: &Vec<PassPhrase>, cli_args: &Args, attackers: &[AttackerModel]) {
     for l in list {
-        println!("{l}");
+        println!("{}", format_passphrase(l, cli_args, attackers));
+    }
+}
+
+pub fn format_passphrase(pp: &PassPhrase, cli_args: &Args, attackers: &[AttackerModel]) -> String {
+    let mut text = if cli_args.show_entropy {
+        format!("{pp}  ({:.1} bits)", pp.entropy())
+    } else {
+        pp.to_string()
+    };
+    if cli_args.show_crack_time {
+        text += &format_crack_times(pp.entropy(), attackers);
+    }
+    // One roll per line, in the order `sppg dice` asks for them
+    if cli_args.show_rolls {
+        for roll in pp.rolls() {
+            text += &format!("\n    {:<5} {}", roll.code, roll.label);
+        }
     }
+
+    text
 }
@@ -1,7 +1,83 @@
-use sppg::{cli::process_command_line, iterate, print_passphrases};
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal},
+    process,
+};
+
+use sppg::{
+    check::{check_phrase, format_strength, read_passphrase},
+    cli::{process_command_line, Args, Command},
+    decode::{decode_phrase, format_decoded, Found},
+    dice::{dice_passphrase, Rolls},
+    explain_policy, format_passphrase, iterate, load_attackers, max_length_warning,
+    print_passphrases,
+};
 
 fn main() {
     let cli_args = process_command_line();
-    let list = iterate(&cli_args);
-    print_passphrases(&list);
+    if cli_args.seed.is_some() {
+        eprintln!("WARNING: --seed is for testing only. Anyone who knows the seed can");
+        eprintln!("WARNING: reproduce these passphrases. Do NOT use them for anything real.");
+    }
+    // The config file is only read when it's needed, so that a broken one
+    // doesn't get in the way of anything else
+    let attackers = if cli_args.show_crack_time || cli_args.command == Some(Command::Check) {
+        or_exit(load_attackers(&cli_args))
+    } else {
+        Vec::new()
+    };
+
+    match &cli_args.command {
+        Some(Command::Decode { phrase }) => {
+            let decoded = or_exit(decode_phrase(&cli_args, phrase));
+            if decoded.iter().all(|d| d.token.trim().is_empty()) {
+                eprintln!("error: no words to decode");
+                process::exit(1);
+            }
+            print!("{}", format_decoded(&decoded));
+            let missing = decoded
+                .iter()
+                .filter(|d| d.found == Found::NotInList)
+                .count();
+            if missing > 0 {
+                eprintln!("error: words not in the word list: {missing}");
+                process::exit(1);
+            }
+        }
+        Some(Command::Check) => {
+            let phrase = or_exit(read_passphrase());
+            let strength = or_exit(check_phrase(&cli_args, &phrase));
+            print!("{}", format_strength(&strength, &attackers));
+        }
+        Some(Command::Dice) => {
+            warn_of_max_length(&cli_args);
+            // Only ask for each roll when someone is there to answer
+            let stdin = io::stdin();
+            let prompt = stdin.is_terminal().then(io::stderr);
+            let mut rolls = Rolls::new(stdin.lock(), prompt);
+            let pp = or_exit(dice_passphrase(&cli_args, &mut rolls));
+            println!("{}", format_passphrase(&pp, &cli_args, &attackers));
+        }
+        None if cli_args.explain_policy => {
+            print!("{}", or_exit(explain_policy(&cli_args)));
+        }
+        None => {
+            warn_of_max_length(&cli_args);
+            let list = or_exit(iterate(&cli_args));
+            print_passphrases(&list, &cli_args, &attackers);
+        }
+    }
+}
+
+fn warn_of_max_length(cli_args: &Args) {
+    if let Some(warning) = or_exit(max_length_warning(cli_args)) {
+        eprintln!("WARNING: {warning}");
+    }
+}
+
+fn or_exit<T, E: Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        process::exit(1);
+    })
 }
@@ -1,26 +1,58 @@
 use itertools::Itertools;
 use std::ops::{Index, IndexMut};
 
-const CHAR_COUNT_MIN: usize = 19;
-const WORD_COUNT_MIN: usize = 4;
+pub(crate) const CHAR_COUNT_MIN: usize = 19;
+pub(crate) const WORD_COUNT_MIN: usize = 4;
 const QUALITY_CHAR_COUNT_MIN: usize = 8;
-const QUALITY_WORD_COUNT_MIN: usize = 2;
+pub(crate) const QUALITY_WORD_COUNT_MIN: usize = 2;
+
+// A choice made while building a phrase, as the roll of dice that makes
+// the same choice in `sppg dice`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Roll {
+    pub code: String,
+    pub label: String,
+}
 
 #[derive(Clone, Debug)]
 pub struct PassPhrase {
-    separator: char,
+    separator: String,
     inner: Vec<String>,
+    entropy: f64,
+    rolls: Vec<Roll>,
+    keeps_rolls: bool,
 }
 
 impl PassPhrase {
     pub fn new(sep: Option<char>) -> Self {
-        let separator = sep.unwrap_or(' ');
+        let separator = sep.unwrap_or(' ').to_string();
         Self {
             separator,
             inner: Vec::<String>::new(),
+            entropy: 0.0,
+            rolls: Vec::<Roll>::new(),
+            keeps_rolls: false,
+        }
+    }
+
+    // A phrase whose words are written one after the other, as in CamelCase
+    pub fn joined() -> Self {
+        Self {
+            separator: String::new(),
+            ..Self::new(None)
         }
     }
 
+    // Whether the words are written with nothing between them
+    pub fn is_joined(&self) -> bool {
+        self.separator.is_empty()
+    }
+
+    // The character written between words, if there is one
+    pub fn separator(&self) -> Option<char> {
+        self.separator.chars().next()
+    }
+
     pub fn len(&self) -> usize {
         self.inner.len()
     }
@@ -35,12 +67,58 @@ impl PassPhrase {
         self
     }
 
+    // The bits of entropy of the random choices that made this phrase, as
+    // recorded by the generator. A phrase built by hand has none.
+    pub fn entropy(&self) -> f64 {
+        self.entropy
+    }
+
+    pub fn add_entropy(&mut self, bits: f64) -> &mut Self {
+        self.entropy += bits;
+
+        self
+    }
+
+    // The rolls that made this phrase, in the order they were made
+    pub fn rolls(&self) -> &[Roll] {
+        &self.rolls
+    }
+
+    // Rolls are only recorded once asked for, as their codes and labels
+    // cost more to build than the rest of a phrase
+    pub fn keep_rolls(&mut self) -> &mut Self {
+        self.keeps_rolls = true;
+
+        self
+    }
+
+    pub fn keeps_rolls(&self) -> bool {
+        self.keeps_rolls
+    }
+
+    pub fn add_roll(&mut self, code: String, label: &str) -> &mut Self {
+        if self.keeps_rolls {
+            self.rolls.push(Roll {
+                code,
+                label: label.into(),
+            });
+        }
+
+        self
+    }
+
+    pub fn insert(&mut self, index: usize, word: &str) -> &mut Self {
+        self.inner.insert(index, word.into());
+
+        self
+    }
+
     pub fn is_insecure(&self) -> bool {
         // All lowercase with less than 4 words is insecure
         let word_count = self.len();
 
         // we should count the spaces between the words
-        let spaces = self.len() - 1;
+        let spaces = (self.len() - 1) * self.separator.chars().count();
         let mut char_length = 0;
         for word in &self.inner {
             char_length += word.chars().count();
@@ -102,8 +180,7 @@ impl Default for PassPhrase {
 
 impl std::fmt::Display for PassPhrase {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let separator = &self.separator.to_string();
-        let pp = self.inner.iter().format(separator);
+        let pp = self.inner.iter().format(&self.separator);
 
         write!(f, "{}", pp)
     }
@@ -171,6 +248,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_is_insecure_accented() {
+        // 19 characters, but more than 19 bytes
+        let mut passphrase = PassPhrase::new(None);
+        passphrase
+            .push("été")
+            .push("über")
+            .push("año")
+            .push("façade");
+
+        assert!(
+            !passphrase.is_insecure(),
+            "accented letters are counted as one char each"
+        );
+
+        let mut passphrase = PassPhrase::new(None);
+        passphrase.push("!é").push("Ñandú");
+
+        assert!(
+            !passphrase.is_insecure(),
+            "an accented capital counts towards quality"
+        );
+    }
+
     #[test]
     fn test_is_insecure_words3() {
         let mut passphrase = PassPhrase::new(None);
@@ -193,6 +294,39 @@ mod test {
         );
     }
 
+    #[test]
+    fn insert_word() {
+        let mut passphrase = PassPhrase::new(None);
+        passphrase
+            .push("some")
+            .push("phrase")
+            .insert(1, "!")
+            .insert(3, "?");
+
+        assert_eq!(passphrase.to_string(), "some ! phrase ?");
+    }
+
+    #[test]
+    fn entropy_is_accumulated() {
+        let mut passphrase = PassPhrase::new(None);
+        assert_eq!(passphrase.entropy(), 0.0);
+
+        passphrase
+            .push("some")
+            .add_entropy(12.5)
+            .push("phrase")
+            .add_entropy(12.5);
+        assert_eq!(passphrase.entropy(), 25.0);
+    }
+
+    #[test]
+    fn joined_words_have_no_separator() {
+        let mut passphrase = PassPhrase::joined();
+        passphrase.push("Correct").push("Horse").push("Battery");
+
+        assert_eq!(passphrase.to_string(), "CorrectHorseBattery");
+    }
+
     #[test]
     fn default_impl() {
         #[derive(Default)]
@@ -0,0 +1,684 @@
+use std::{collections::HashMap, f64::consts::LN_2};
+
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    build_passphrase,
+    passphrase::{CHAR_COUNT_MIN, QUALITY_WORD_COUNT_MIN, WORD_COUNT_MIN},
+    placement_entropy,
+    recipe::{Recipe, Words},
+    rules::RulesError,
+    wordlist::WordList,
+    Case, Error, Placement,
+};
+
+// A rejection rate that can't be counted exactly is estimated from batches
+// of generated phrases, until the entropy lost is known to within
+// REJECTION_MARGIN bits or REJECTION_SAMPLES have been made
+const REJECTION_BATCH: usize = 1_000;
+const REJECTION_SAMPLES: usize = 20_000;
+const REJECTION_MARGIN: f64 = 0.02;
+
+// The phrases looked at to find the rule that throws all of them away
+const BLAME_SAMPLES: usize = 100;
+
+// How generated passphrases are judged to be secure enough
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Policy {
+    // Every phrase is kept; its strength is set by the word count or by
+    // --min-entropy
+    #[default]
+    Entropy,
+    // Phrases that fail PassPhrase::is_insecure() are thrown away
+    Legacy,
+}
+
+// The bits of entropy every phrase of `word_count` words is guaranteed to
+// have, whichever words end up being chosen.
+pub fn minimum_entropy<W: WordList + ?Sized>(
+    recipe: &Recipe,
+    words: &Words<W>,
+    word_count: usize,
+) -> f64 {
+    let mut bits = match recipe.room(words, word_count) {
+        Some(room) => capped_words_entropy(recipe, words, word_count, room),
+        None => word_count as f64 * words.entropy_per_word(),
+    };
+
+    // A word in upper case is only chosen from those with letters, so
+    // --case only guarantees bits when every word has some
+    if word_count > 0 && words.every_word_cased() {
+        bits += match recipe.case {
+            Case::UpperWord => (word_count as f64).log2(),
+            Case::Random => word_count as f64,
+            Case::Lower | Case::Title | Case::Camel => 0.0,
+        };
+    }
+
+    // A capital is only counted when it's added, and a phrase may have no
+    // letter to capitalize, so -c guarantees nothing. Characters are added
+    // for as long as there are gaps left for them.
+    if word_count > 0 {
+        let gaps = min_gaps(
+            word_count,
+            recipe.placement,
+            recipe.passphrase().is_joined(),
+        );
+        let mut left = gaps;
+        let mut placed = Vec::new();
+        for insert in &recipe.inserts {
+            let count = insert.count.min(left);
+            bits += count as f64 * insert.entropy();
+            placed.push(count);
+            left -= count;
+        }
+        bits += placement_entropy(gaps, &placed);
+    }
+
+    bits
+}
+
+// The least entropy the words of a phrase can have when each is drawn from
+// those that leave room for the rest. Long words leave fewer to choose from
+// later, so every way of using up the room is followed, from the last word
+// back to the first.
+fn capped_words_entropy<W: WordList + ?Sized>(
+    recipe: &Recipe,
+    words: &Words<W>,
+    word_count: usize,
+    room: usize,
+) -> f64 {
+    let lengths = words.distinct_lengths();
+    // least[r] is the least entropy of the words still to be drawn with r
+    // characters of room left for them
+    let mut least = vec![0.0; room + 1];
+    for words_left in 0..word_count {
+        let mut next = vec![f64::INFINITY; room + 1];
+        for (r, bits) in next.iter_mut().enumerate() {
+            let limit = recipe.word_limit(words, r, words_left);
+            let count = words.fitting(limit);
+            if count == 0 {
+                continue;
+            }
+            let rest = lengths
+                .iter()
+                .take_while(|len| **len <= limit)
+                .map(|len| least[r - len])
+                .fold(f64::INFINITY, f64::min);
+            *bits = (count as f64).log2() + rest;
+        }
+        least = next;
+    }
+
+    least[room]
+}
+
+// Every word has at least one grapheme, so at least two gaps of its own,
+// of which words with nothing between them share one
+fn min_gaps(word_count: usize, placement: Placement, joined: bool) -> usize {
+    match placement {
+        _ if joined => word_count + 1,
+        Placement::Word => 2 * word_count,
+        Placement::Token => word_count + 1,
+        Placement::Any => 3 * word_count + 1,
+    }
+}
+
+// The fewest words that guarantee at least `target` bits of entropy. It
+// fails if the words in the list carry no entropy, or if the phrases grow
+// past the maxlength of the rules first.
+pub fn words_for_entropy<W, R>(
+    recipe: &Recipe,
+    words: &Words<W>,
+    target: f64,
+    rng: &mut R,
+) -> Result<usize, Error>
+where
+    W: WordList + ?Sized,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    if words.entropy_per_word() <= 0.0 {
+        return Err(Error::NoEntropy);
+    }
+
+    let mut word_count = 1;
+    loop {
+        recipe.check_max_length(words, word_count)?;
+        // The rejection rate is only worked out once the words alone are
+        // enough, as it may have to be estimated by sampling.
+        let bits = minimum_entropy(recipe, words, word_count);
+        if bits >= target
+            && bits - rejection(recipe, words, word_count, rng).entropy_loss() >= target
+        {
+            return Ok(word_count);
+        }
+        word_count += 1;
+    }
+}
+
+// The share of generated phrases a policy or the rules throw away
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rejection {
+    pub rate: f64,
+    // None if the rate was counted exactly, otherwise the number of
+    // phrases it was estimated from
+    pub samples: Option<usize>,
+}
+
+impl Rejection {
+    // A kept phrase is 1 / (1 - rate) times as likely as its own choices
+    // say, since the rejected ones never make it out. When every phrase is
+    // rejected the loss is infinite.
+    pub fn entropy_loss(&self) -> f64 {
+        (1.0 / (1.0 - self.rate)).log2()
+    }
+
+    // Half the width of the 95% confidence interval of entropy_loss(), or
+    // 0 if the rate was counted exactly
+    pub fn margin(&self) -> f64 {
+        match self.samples {
+            Some(samples) => {
+                1.96 * (self.rate / ((1.0 - self.rate) * samples as f64)).sqrt() / LN_2
+            }
+            None => 0.0,
+        }
+    }
+}
+
+// Fails if none of the `wanted` phrases of `word_count` words made from
+// `recipe` can be kept, naming the rule that threw away the most of a few of them. A rate of 1
+// that was counted exactly, which only the policy gives, is left to the
+// attempts at generating phrases to report.
+pub(crate) fn some_are_kept<W, R>(
+    recipe: &Recipe,
+    words: &Words<W>,
+    word_count: usize,
+    rejection: &Rejection,
+    wanted: usize,
+    rng: &mut R,
+) -> Result<(), Error>
+where
+    W: WordList + ?Sized,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    let Some(samples) = rejection.samples.filter(|_| rejection.rate >= 1.0) else {
+        return Ok(());
+    };
+
+    let mut broken = HashMap::<String, usize>::new();
+    if let Some(rules) = &recipe.rules {
+        for _ in 0..BLAME_SAMPLES {
+            let phrase = build_passphrase(recipe, words, word_count, rng).to_string();
+            if let Some(rule) = rules.broken_by(&phrase) {
+                *broken.entry(rule).or_default() += 1;
+            }
+        }
+    }
+    match broken.into_iter().max_by_key(|(_, count)| *count) {
+        Some((rule, _)) => Err(Error::Rules(RulesError::Unsatisfiable {
+            rule,
+            reason: format!("it threw away all {samples} phrases of {word_count} words tried"),
+        })),
+        None => Err(Error::TooManyAttempts {
+            wanted,
+            found: 0,
+            attempts: samples,
+        }),
+    }
+}
+
+// How many of the phrases of `word_count` words made from `recipe` the
+// policy and rules reject. The legacy policy only looks at the length of a
+// plain phrase of 4 or more words, which is counted exactly; any other
+// phrase is judged on its characters as well, so the rate is estimated.
+pub fn rejection<W, R>(
+    recipe: &Recipe,
+    words: &Words<W>,
+    word_count: usize,
+    rng: &mut R,
+) -> Rejection
+where
+    W: WordList + ?Sized,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    let exact = |rate| Rejection {
+        rate,
+        samples: None,
+    };
+    if !recipe.may_reject() {
+        return exact(0.0);
+    }
+
+    // Without rules only the legacy policy rejects anything, and the words
+    // are only independent of each other without a maximum length
+    let plain = recipe.rules.is_none()
+        && recipe.max_length.is_none()
+        && recipe.capitals == 0
+        && recipe.case == Case::Lower
+        && recipe.inserts.is_empty();
+    if plain && word_count < QUALITY_WORD_COUNT_MIN {
+        exact(1.0)
+    } else if plain && word_count >= WORD_COUNT_MIN {
+        exact(length_rejection(words, word_count))
+    } else {
+        let mut rejected = 0;
+        let mut estimate = Rejection {
+            rate: 0.0,
+            samples: Some(0),
+        };
+        for samples in (REJECTION_BATCH..=REJECTION_SAMPLES).step_by(REJECTION_BATCH) {
+            rejected += (0..REJECTION_BATCH)
+                .filter(|_| !recipe.accepts(&build_passphrase(recipe, words, word_count, rng)))
+                .count();
+            estimate = Rejection {
+                rate: rejected as f64 / samples as f64,
+                samples: Some(samples),
+            };
+            if estimate.margin() <= REJECTION_MARGIN {
+                break;
+            }
+        }
+
+        estimate
+    }
+}
+
+// The share of phrases of `word_count` words that are shorter than
+// CHAR_COUNT_MIN, found by adding up the chances of every total length
+// below it one word at a time.
+fn length_rejection<W: WordList + ?Sized>(word_list: &W, word_count: usize) -> f64 {
+    // The spaces between the words count towards the length
+    let limit = CHAR_COUNT_MIN.saturating_sub(word_count.saturating_sub(1));
+    if limit == 0 {
+        return 0.0;
+    }
+
+    let mut word_share = vec![0.0; limit];
+    for index in 0..word_list.len() {
+        let len = word_list.word(index).map_or(0, |w| w.chars().count());
+        if len < limit {
+            word_share[len] += 1.0 / word_list.len() as f64;
+        }
+    }
+
+    let mut share = vec![0.0; limit];
+    share[0] = 1.0;
+    for _ in 0..word_count {
+        let mut next = vec![0.0; limit];
+        for (total, s) in share.iter().enumerate() {
+            for (len, w) in word_share.iter().enumerate().take(limit - total) {
+                next[total + len] += s * w;
+            }
+        }
+        share = next;
+    }
+
+    share.iter().sum()
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{cli::Args, passphrase::PassPhrase, EFF, ORIGINAL};
+
+    fn recipe(args: &[&str]) -> Recipe {
+        Recipe::from_args(&Args::parse_from(args)).unwrap()
+    }
+
+    #[test]
+    fn minimum_entropy_of_words() {
+        let recipe = recipe(&["sppg"]);
+
+        assert!(
+            (minimum_entropy(&recipe, &recipe.words(&ORIGINAL).unwrap(), 5) - 5.0 * 7776f64.log2())
+                .abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn minimum_entropy_with_special_char() {
+        let recipe = recipe(&["sppg", "-s"]);
+        let expected = 4.0 * 7776f64.log2() + 36f64.log2() + 8f64.log2();
+
+        assert!(
+            (minimum_entropy(&recipe, &recipe.words(&ORIGINAL).unwrap(), 4) - expected).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn minimum_entropy_with_digits_and_special_chars() {
+        // 3 of the 8 gaps, 1 for the digit and 2 for the special chars, which
+        // are the 26 of the table that aren't digits
+        let recipe = recipe(&["sppg", "--specials", "2", "--digits", "1"]);
+        let expected =
+            4.0 * 7776f64.log2() + 10f64.log2() + 2.0 * 26f64.log2() + (8.0 * 21.0f64).log2();
+
+        assert!(
+            (minimum_entropy(&recipe, &recipe.words(&ORIGINAL).unwrap(), 4) - expected).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn minimum_entropy_of_joined_words() {
+        // Words with nothing between them share the gap at their boundary,
+        // so 4 words have at least 5 gaps
+        let recipe = recipe(&[
+            "sppg",
+            "--case",
+            "camel",
+            "-s",
+            "--special-placement",
+            "any",
+        ]);
+        let expected = 4.0 * 7776f64.log2() + 36f64.log2() + 5f64.log2();
+
+        assert!(
+            (minimum_entropy(&recipe, &recipe.words(&EFF).unwrap(), 4) - expected).abs() < 1e-9
+        );
+    }
+
+    #[test]
+    fn minimum_entropy_of_case_styles() {
+        // Every word of the EFF list has letters, but not every one of the
+        // original list
+        let bits = |list: &dyn WordList, case: &str| {
+            let recipe = recipe(&["sppg", "--case", case]);
+            minimum_entropy(&recipe, &recipe.words(list).unwrap(), 4)
+                - 4.0 * list.entropy_per_word()
+        };
+
+        assert_eq!(bits(&EFF, "title"), 0.0);
+        assert_eq!(bits(&EFF, "camel"), 0.0);
+        assert_eq!(bits(&EFF, "upper-word"), 2.0);
+        assert_eq!(bits(&EFF, "random"), 4.0);
+        assert_eq!(bits(&ORIGINAL, "random"), 0.0);
+    }
+
+    #[test]
+    fn minimum_entropy_ignores_capital() {
+        let recipe = recipe(&["sppg", "-c"]);
+
+        assert!(
+            (minimum_entropy(&recipe, &recipe.words(&ORIGINAL).unwrap(), 5) - 5.0 * 7776f64.log2())
+                .abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn minimum_entropy_under_max_length() {
+        // With 7 characters for 2 words any of the 6 may come first, but
+        // after "ffffff" only "a" is left
+        let words: Vec<String> = (0..6)
+            .map(|len| ((b'a' + len) as char).to_string().repeat(len as usize + 1))
+            .collect();
+        let recipe = recipe(&["sppg", "--max-length", "8"]);
+
+        assert!(
+            (minimum_entropy(&recipe, &recipe.words(&words).unwrap(), 2) - 6f64.log2()).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn words_for_target_entropy() {
+        let recipe = recipe(&["sppg"]);
+
+        assert_eq!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                60.0,
+                &mut thread_rng()
+            )
+            .ok(),
+            Some(5)
+        );
+        assert_eq!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                64.6,
+                &mut thread_rng()
+            )
+            .ok(),
+            Some(5)
+        );
+        assert_eq!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                64.7,
+                &mut thread_rng()
+            )
+            .ok(),
+            Some(6)
+        );
+        assert_eq!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                1.0,
+                &mut thread_rng()
+            )
+            .ok(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn words_for_target_entropy_with_special_char() {
+        // 4 words are 51.7 bits, and a special char adds at least 8.2
+        let recipe = recipe(&["sppg", "-s"]);
+
+        assert_eq!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                59.0,
+                &mut thread_rng()
+            )
+            .ok(),
+            Some(4)
+        );
+        assert_eq!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                60.0,
+                &mut thread_rng()
+            )
+            .ok(),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn words_for_target_entropy_with_legacy_policy() {
+        // 5 words are 64.62 bits, less the 0.01 bits lost to the 0.6% of
+        // phrases that are too short
+        let recipe = recipe(&["sppg", "--policy", "legacy"]);
+
+        assert_eq!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                64.6,
+                &mut thread_rng()
+            )
+            .ok(),
+            Some(5)
+        );
+        assert_eq!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                64.62,
+                &mut thread_rng()
+            )
+            .ok(),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn words_for_target_entropy_stop_at_maxlength() {
+        // The shortest words of the list are 1 character long, so 4 words
+        // and the spaces between them are at least 7 characters
+        let recipe = recipe(&["sppg", "--rules", "maxlength: 6"]);
+
+        assert!(matches!(
+            words_for_entropy(
+                &recipe,
+                &recipe.words(&ORIGINAL).unwrap(),
+                60.0,
+                &mut thread_rng()
+            ),
+            Err(Error::Rules(_))
+        ));
+    }
+
+    #[test]
+    fn minimum_entropy_of_each_required_char() {
+        // A digit in one of 8 gaps, then a special char in one of the 7 left
+        let recipe = recipe(&[
+            "sppg",
+            "--rules",
+            "required: digit; required: [!?]; allowed: lower, [ ]",
+        ]);
+        // Only the words of lower case letters are left
+        let words = recipe.words(&ORIGINAL).unwrap();
+        let expected =
+            4.0 * words.entropy_per_word() + 10f64.log2() + 8f64.log2() + 2f64.log2() + 7f64.log2();
+
+        assert!((minimum_entropy(&recipe, &words, 4) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rules_reject_what_they_do_not_allow() {
+        // "a-a" is the only phrase shorter than 5 characters
+        let words: Vec<String> = ["a", "bcd"].map(String::from).into();
+        let recipe = recipe(&["sppg", "--rules", "minlength: 5; allowed: lower, [-]"]);
+        let rejection = rejection(
+            &recipe,
+            &recipe.words(&words).unwrap(),
+            2,
+            &mut thread_rng(),
+        );
+
+        // Sampling stops once the loss is known well enough
+        assert!(rejection.samples < Some(REJECTION_SAMPLES));
+        assert!(rejection.margin() <= REJECTION_MARGIN);
+        assert!(
+            (rejection.rate - 0.25).abs() < 0.02,
+            "rate = {}",
+            rejection.rate
+        );
+    }
+
+    #[test]
+    fn entropy_policy_rejects_nothing() {
+        let recipe = recipe(&["sppg", "-w", "1"]);
+        let rejection = rejection(
+            &recipe,
+            &recipe.words(&ORIGINAL).unwrap(),
+            1,
+            &mut thread_rng(),
+        );
+
+        assert_eq!(rejection.rate, 0.0);
+        assert_eq!(rejection.samples, None);
+        assert_eq!(rejection.entropy_loss(), 0.0);
+    }
+
+    #[test]
+    fn legacy_policy_rejects_every_single_word() {
+        let recipe = recipe(&["sppg", "--policy", "legacy"]);
+        let rejection = rejection(
+            &recipe,
+            &recipe.words(&ORIGINAL).unwrap(),
+            1,
+            &mut thread_rng(),
+        );
+
+        assert_eq!(rejection.rate, 1.0);
+        assert_eq!(rejection.entropy_loss(), f64::INFINITY);
+    }
+
+    #[test]
+    fn rules_that_reject_everything_are_unsatisfiable() {
+        let recipe = recipe(&["sppg", "--rules", "max-consecutive: 0"]);
+        let words = recipe.words(&ORIGINAL).unwrap();
+        let mut rng = thread_rng();
+        let rejection = rejection(&recipe, &words, 4, &mut rng);
+
+        assert_eq!(rejection.rate, 1.0);
+        assert!(matches!(
+            some_are_kept(&recipe, &words, 4, &rejection, 1, &mut rng),
+            Err(Error::Rules(RulesError::Unsatisfiable { rule, .. })) if rule == "max-consecutive: 0"
+        ));
+    }
+
+    #[test]
+    fn length_rejection_matches_every_phrase() {
+        // Words of 1 to 6 letters, so that 4 of them are often too short
+        let words: Vec<String> = (1..=6).map(|len| "x".repeat(len)).collect();
+        let recipe = recipe(&["sppg", "--policy", "legacy"]);
+        let mut rejected = 0;
+        for i in 0..6usize.pow(4) {
+            let mut passphrase = PassPhrase::new(None);
+            for digit in [i / 216, i / 36 % 6, i / 6 % 6, i % 6] {
+                passphrase.push(&words[digit]);
+            }
+            if passphrase.is_insecure() {
+                rejected += 1;
+            }
+        }
+        let rejection = rejection(
+            &recipe,
+            &recipe.words(&words).unwrap(),
+            4,
+            &mut thread_rng(),
+        );
+
+        assert_eq!(rejection.samples, None, "the rate is exact");
+        assert!((rejection.rate - rejected as f64 / 1296.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sampled_rejection_is_close() {
+        // With a capital and a special char in a word "abc abc" is kept,
+        // "abc de" is 7 chars, one short of the legacy minimum, and "de de"
+        // is shorter still. "abc de" is only kept when the special char is
+        // a word of its own, and adds a space, which is 3 of its 10 gaps.
+        let words: Vec<String> = ["abc", "de"].map(String::from).into();
+        let recipe = recipe(&[
+            "sppg",
+            "--policy",
+            "legacy",
+            "-q",
+            "--special-placement",
+            "any",
+        ]);
+        let rejection = rejection(
+            &recipe,
+            &recipe.words(&words).unwrap(),
+            2,
+            &mut thread_rng(),
+        );
+
+        // 1/4 * 0 + 1/2 * 7/10 + 1/4 * 1
+        assert!(rejection.samples.is_some());
+        assert!(
+            (rejection.rate - 0.6).abs() < 0.02,
+            "rate = {}",
+            rejection.rate
+        );
+    }
+}
@@ -0,0 +1,956 @@
+use std::cell::OnceCell;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    cli::Args,
+    has_case,
+    passphrase::PassPhrase,
+    policy::Policy,
+    rules::{parse_rules, Rules, RulesError},
+    title_case,
+    wordlist::{filtered::FilteredList, special_char::special_char_set},
+    Case, Error, Placement, WordList, WordListError,
+};
+
+// The separators tried in turn when the rules don't allow the one given
+// with -S, or a space if none was
+const SEPARATORS: [char; 9] = [' ', '-', '_', '.', ',', ':', '+', '=', '~'];
+
+// `count` characters added to a phrase, each chosen uniformly from
+// `chars`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Insert {
+    // What the character is called in rolls and dice prompts
+    pub what: &'static str,
+    pub chars: Vec<char>,
+    pub count: usize,
+}
+
+impl Insert {
+    // The special characters of -s, from the set named by --special-chars
+    // or the 6x6 table if there is none
+    pub fn special_chars(set: Option<&str>, count: usize) -> Self {
+        Self {
+            what: "special character",
+            chars: special_char_set(set),
+            count,
+        }
+    }
+
+    pub fn digits(count: usize) -> Self {
+        Self {
+            what: "digit",
+            chars: ('0'..='9').collect(),
+            count,
+        }
+    }
+
+    pub fn entropy(&self) -> f64 {
+        (self.chars.len() as f64).log2()
+    }
+}
+
+// How each phrase is put together from its words, as set by the options
+// and by any --rules
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipe {
+    pub separator: Option<char>,
+    // The words are written in upper case when the rules allow no lower
+    // case letters
+    pub upper_case: bool,
+    pub case: Case,
+    pub capitals: usize,
+    // Added after the capitals, each character into a gap of its own
+    // chosen by `placement`. No two share a character, so that a phrase
+    // shows which of them each one is.
+    pub inserts: Vec<Insert>,
+    pub placement: Placement,
+    // The most characters a phrase may have, separators included
+    pub max_length: Option<usize>,
+    pub policy: Policy,
+    pub rules: Option<Rules>,
+    // Whether each phrase keeps the rolls that made it
+    pub show_rolls: bool,
+}
+
+impl Recipe {
+    pub fn from_args(cli_args: &Args) -> Result<Self, Error> {
+        let mut recipe = Self {
+            separator: cli_args.separator,
+            upper_case: false,
+            case: cli_args.case,
+            capitals: cli_args
+                .capitals
+                .map_or(cli_args.capital_char().into(), usize::from),
+            inserts: Vec::new(),
+            placement: cli_args.special_placement,
+            max_length: cli_args.max_length.map(usize::from),
+            policy: cli_args.policy,
+            rules: None,
+            show_rolls: cli_args.show_rolls,
+        };
+        if cli_args.digits > 0 {
+            recipe.inserts.push(Insert::digits(cli_args.digits.into()));
+        }
+        let specials = cli_args
+            .specials
+            .map_or(cli_args.special_char().into(), usize::from);
+        if specials > 0 {
+            recipe.inserts.push(Insert::special_chars(
+                cli_args.special_chars.as_deref(),
+                specials,
+            ));
+        }
+        if let Some(text) = &cli_args.rules {
+            recipe.follow(parse_rules(text)?)?;
+        }
+        recipe.keep_inserts_apart()?;
+
+        Ok(recipe)
+    }
+
+    // Takes the separator out of every insert, and the characters an
+    // earlier insert adds out of the later ones, so that digits among the
+    // special characters are left to --digits
+    fn keep_inserts_apart(&mut self) -> Result<(), Error> {
+        if let Some(separator) = self.passphrase().separator() {
+            for insert in &mut self.inserts {
+                insert.chars.retain(|ch| *ch != separator);
+                if insert.chars.is_empty() {
+                    return Err(Error::SharedChars {
+                        what: insert.what,
+                        other: "separator",
+                    });
+                }
+            }
+        }
+        for i in 1..self.inserts.len() {
+            let (earlier, later) = self.inserts.split_at_mut(i);
+            let insert = &mut later[0];
+            let other = earlier
+                .iter()
+                .find(|other| other.chars.iter().any(|ch| insert.chars.contains(ch)));
+            insert
+                .chars
+                .retain(|ch| !earlier.iter().any(|other| other.chars.contains(ch)));
+            if let (Some(other), true) = (other, insert.chars.is_empty()) {
+                return Err(Error::SharedChars {
+                    what: insert.what,
+                    other: other.what,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // The number of characters all the inserts add
+    pub fn insert_count(&self) -> usize {
+        self.inserts.iter().map(|insert| insert.count).sum()
+    }
+
+    // Makes every choice come from the characters the rules allow, and
+    // adds what each required class needs unless the phrase always has it.
+    fn follow(&mut self, rules: Rules) -> Result<(), RulesError> {
+        let allowed = rules.allowed();
+        let unmet = |reason: String| RulesError::Unsatisfiable {
+            rule: rules.allowed_rule(),
+            reason,
+        };
+
+        let any_upper = ('A'..='Z').any(|ch| allowed.contains(ch));
+        if !('a'..='z').any(|ch| allowed.contains(ch)) {
+            if !any_upper {
+                return Err(unmet("the words need letters, which aren't allowed".into()));
+            }
+            // Words already in upper case have no case left to style
+            self.upper_case = true;
+            self.case = Case::Lower;
+        }
+        if self.capitals > 0 && !any_upper {
+            return Err(unmet(
+                "-c adds a capital letter, which isn't allowed".into(),
+            ));
+        }
+        if self.case != Case::Lower && !any_upper {
+            return Err(unmet(
+                "--case adds capital letters, which aren't allowed".into(),
+            ));
+        }
+
+        for insert in &mut self.inserts {
+            insert.chars.retain(|ch| allowed.contains(*ch));
+            if insert.chars.is_empty() {
+                return Err(unmet(format!(
+                    "none of the {}s to add is allowed",
+                    insert.what
+                )));
+            }
+        }
+
+        // A separator that is also one of the characters to add would be
+        // taken out of them, so one that isn't is picked if it can be
+        let added = |separator: &char| {
+            self.inserts
+                .iter()
+                .any(|insert| insert.chars.contains(separator))
+        };
+        let separator = match self.separator {
+            _ if self.case == Case::Camel => None,
+            Some(separator) if allowed.contains(separator) => Some(separator),
+            Some(separator) => {
+                return Err(unmet(format!("the separator '{separator}' isn't allowed")));
+            }
+            None => Some(
+                SEPARATORS
+                    .into_iter()
+                    .filter(|separator| allowed.contains(*separator))
+                    .min_by_key(added)
+                    .ok_or_else(|| {
+                        let separators = String::from_iter(SEPARATORS);
+                        unmet(format!(
+                            "none of the separators \"{separators}\" is allowed"
+                        ))
+                    })?,
+            ),
+        };
+        if separator.is_some() {
+            self.separator = separator;
+        }
+        if let Some(max) = rules.max_length {
+            self.max_length = Some(self.max_length.map_or(max, |cap| cap.min(max)));
+        }
+
+        for (_, class) in &rules.required {
+            let letters = if self.upper_case {
+                'A'..='Z'
+            } else {
+                'a'..='z'
+            };
+            let met = class.is_unicode()
+                || separator.is_some_and(|separator| class.contains(separator))
+                || class.contains_all(letters)
+                || ((self.capitals > 0 || self.case.always_upper())
+                    && class.contains_all('A'..='Z'))
+                || self
+                    .inserts
+                    .iter()
+                    .any(|insert| class.contains_all(insert.chars.iter().copied()));
+            if met {
+                continue;
+            }
+            if class.contains_all('A'..='Z') {
+                self.capitals = 1;
+                continue;
+            }
+            // Digits go first, as they do for --digits
+            let chars = class.chars();
+            if chars.iter().all(char::is_ascii_digit) {
+                let insert = Insert {
+                    what: "digit",
+                    chars,
+                    count: 1,
+                };
+                self.inserts.insert(0, insert);
+            } else {
+                self.inserts.push(Insert {
+                    what: "special character",
+                    chars,
+                    count: 1,
+                });
+            }
+        }
+
+        self.rules = Some(rules);
+
+        Ok(())
+    }
+
+    // An empty phrase to add the words to
+    pub fn passphrase(&self) -> PassPhrase {
+        let mut passphrase = match self.case {
+            Case::Camel => PassPhrase::joined(),
+            _ => PassPhrase::new(self.separator),
+        };
+        if self.show_rolls {
+            passphrase.keep_rolls();
+        }
+
+        passphrase
+    }
+
+    // The most characters a word of the list can take up in a phrase, once
+    // its case is styled
+    pub fn longest_form(&self, word: &str) -> usize {
+        let word = self.render(word);
+        match self.case {
+            Case::Lower => word.chars().count(),
+            Case::Title | Case::Camel => title_case(&word).chars().count(),
+            Case::UpperWord | Case::Random => word
+                .chars()
+                .count()
+                .max(word.to_uppercase().chars().count()),
+        }
+    }
+
+    // A word from the list as it appears in the phrase
+    pub fn render(&self, word: &str) -> String {
+        if self.upper_case {
+            word.to_uppercase()
+        } else {
+            word.into()
+        }
+    }
+
+    // The words of `word_list` made only of characters the rules allow. A
+    // list needs two words to choose from, as a file's does.
+    pub fn words<'a, W: WordList + ?Sized>(
+        &'a self,
+        word_list: &'a W,
+    ) -> Result<Words<'a, W>, Error> {
+        if word_list.len() < 2 {
+            return Err(WordListError::TooShort {
+                found: word_list.len(),
+            }
+            .into());
+        }
+        let list = match &self.rules {
+            None => FilteredList::all(word_list),
+            Some(rules) => {
+                let allowed = rules.allowed();
+                let filtered = FilteredList::new(word_list, |word| {
+                    allowed.contains_all(self.render(word).chars())
+                });
+                if filtered.len() < 2 {
+                    return Err(RulesError::Unsatisfiable {
+                        rule: rules.allowed_rule(),
+                        reason: format!(
+                            "only {} words of list '{}' are made of allowed characters",
+                            filtered.len(),
+                            word_list.name()
+                        ),
+                    }
+                    .into());
+                }
+                filtered
+            }
+        };
+        // Rather than leave every phrase without the capital -c asked for
+        let cased = |index| has_case(&self.render(list.word(index).unwrap_or_default()));
+        if self.capitals > 0 && !(0..list.len()).any(cased) {
+            return Err(Error::NoCapitals {
+                list: word_list.name().into(),
+            });
+        }
+
+        Ok(Words::new(self, list))
+    }
+
+    // The most characters of a phrase of `word_count` words that aren't
+    // the words themselves. A character added between words brings a
+    // separator with it.
+    pub fn reserved<W: WordList + ?Sized>(&self, words: &Words<W>, word_count: usize) -> usize {
+        let insert = match self.placement {
+            Placement::Word => 1,
+            Placement::Token | Placement::Any => 2,
+        };
+        let separators = match self.case {
+            Case::Camel => 0,
+            _ => word_count.saturating_sub(1),
+        };
+
+        separators + self.insert_count() * insert + self.capitals * words.capital_growth()
+    }
+
+    // The characters the words of a phrase may take up under the maximum
+    // length, if there is one
+    pub fn room<W: WordList + ?Sized>(&self, words: &Words<W>, word_count: usize) -> Option<usize> {
+        self.max_length
+            .map(|max| max.saturating_sub(self.reserved(words, word_count)))
+    }
+
+    // The longest word that can be drawn with `room` characters left,
+    // which leaves enough for `words_left` more of the shortest words
+    pub fn word_limit<W: WordList + ?Sized>(
+        &self,
+        words: &Words<W>,
+        room: usize,
+        words_left: usize,
+    ) -> usize {
+        room.saturating_sub(words_left * words.shortest())
+    }
+
+    // Fails if even the shortest words make a phrase of `word_count` words
+    // longer than the maximum length
+    pub fn check_max_length<W: WordList + ?Sized>(
+        &self,
+        words: &Words<W>,
+        word_count: usize,
+    ) -> Result<(), Error> {
+        let Some(max) = self.max_length else {
+            return Ok(());
+        };
+        let shortest = word_count * words.shortest() + self.reserved(words, word_count);
+        if shortest <= max {
+            return Ok(());
+        }
+
+        match self.rules.as_ref().and_then(|rules| rules.max_length) {
+            Some(rule) if rule == max => Err(RulesError::Unsatisfiable {
+                rule: format!("maxlength: {max}"),
+                reason: format!(
+                    "the shortest phrase of {word_count} words is {shortest} characters"
+                ),
+            }
+            .into()),
+            _ => Err(Error::MaxLength {
+                max,
+                word_count,
+                shortest,
+            }),
+        }
+    }
+
+    // Fails if no phrase of `word_count` words can be as short or as long
+    // as it has to be
+    pub fn check_length<W: WordList + ?Sized>(
+        &self,
+        words: &Words<W>,
+        word_count: usize,
+    ) -> Result<(), Error> {
+        self.check_max_length(words, word_count)?;
+        let Some(min) = self.rules.as_ref().and_then(|rules| rules.min_length) else {
+            return Ok(());
+        };
+        let longest = word_count * words.longest() + self.reserved(words, word_count);
+        if longest < min {
+            return Err(RulesError::Unsatisfiable {
+                rule: format!("minlength: {min}"),
+                reason: format!(
+                    "the longest phrase of {word_count} words is {longest} characters; use more words"
+                ),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    // Whether anything may throw a phrase away once it is made
+    pub fn may_reject(&self) -> bool {
+        self.policy == Policy::Legacy || self.rules.is_some()
+    }
+
+    pub fn accepts(&self, pp: &PassPhrase) -> bool {
+        if self.policy == Policy::Legacy && pp.is_insecure() {
+            return false;
+        }
+
+        self.rules
+            .as_ref()
+            .is_none_or(|rules| rules.accepts(&pp.to_string()))
+    }
+}
+
+// The words a recipe draws from: those of the list that the rules allow,
+// and how long each of them is in a phrase
+#[derive(Debug)]
+pub struct Words<'a, W: WordList + ?Sized> {
+    recipe: &'a Recipe,
+    list: FilteredList<'a, W>,
+    // Only worked out when something asks for it, as it takes a walk
+    // through the whole list that plain phrases don't need
+    index: OnceCell<LengthIndex>,
+}
+
+#[derive(Debug)]
+struct LengthIndex {
+    // The index of each word, shortest first, and its length
+    by_length: Vec<usize>,
+    lengths: Vec<usize>,
+    // The most characters capitalizing a letter adds, e.g. 1 for 'ß'
+    capital_growth: usize,
+    every_word_cased: bool,
+}
+
+impl<'a, W: WordList + ?Sized> Words<'a, W> {
+    fn new(recipe: &'a Recipe, list: FilteredList<'a, W>) -> Self {
+        Self {
+            recipe,
+            list,
+            index: OnceCell::new(),
+        }
+    }
+
+    fn index(&self) -> &LengthIndex {
+        self.index.get_or_init(|| {
+            let mut by_length: Vec<(usize, usize)> = Vec::new();
+            let mut capital_growth = 0;
+            let mut every_word_cased = true;
+            for index in 0..self.list.len() {
+                let word = self.list.word(index).unwrap_or_default();
+                by_length.push((self.recipe.longest_form(word), index));
+                let word = self.recipe.render(word);
+                every_word_cased &= has_case(&word);
+                for grapheme in word.graphemes(true) {
+                    let growth = grapheme.to_uppercase().chars().count() - grapheme.chars().count();
+                    capital_growth = capital_growth.max(growth);
+                }
+            }
+            by_length.sort_unstable();
+
+            LengthIndex {
+                lengths: by_length.iter().map(|(len, _)| *len).collect(),
+                by_length: by_length.into_iter().map(|(_, index)| index).collect(),
+                capital_growth,
+                every_word_cased,
+            }
+        })
+    }
+
+    // The most characters capitalizing a letter adds, e.g. 1 for 'ß'
+    pub fn capital_growth(&self) -> usize {
+        self.index().capital_growth
+    }
+
+    // Whether every word has letters that --case can turn to upper case
+    pub fn every_word_cased(&self) -> bool {
+        self.index().every_word_cased
+    }
+
+    pub fn shortest(&self) -> usize {
+        self.index().lengths.first().copied().unwrap_or(0)
+    }
+
+    pub fn longest(&self) -> usize {
+        self.index().lengths.last().copied().unwrap_or(0)
+    }
+
+    // Every length a word has, shortest first
+    pub fn distinct_lengths(&self) -> Vec<usize> {
+        let mut lengths = self.index().lengths.clone();
+        lengths.dedup();
+
+        lengths
+    }
+
+    // The number of words of at most `limit` characters
+    pub fn fitting(&self, limit: usize) -> usize {
+        self.index().lengths.partition_point(|len| *len <= limit)
+    }
+
+    // The index of the nth of the words counted by fitting()
+    pub fn nth_fitting(&self, n: usize) -> usize {
+        self.index().by_length[n]
+    }
+
+    // The index of the word a roll of the dice picks from the whole list,
+    // or None if the rules took it out
+    pub fn index_of_roll(&self, roll: usize) -> Option<usize> {
+        self.list.index_of(roll)
+    }
+}
+
+impl<W: WordList + ?Sized> WordList for Words<'_, W> {
+    fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    fn word(&self, index: usize) -> Option<&str> {
+        self.list.word(index)
+    }
+
+    fn name(&self) -> &str {
+        self.list.name()
+    }
+
+    fn language(&self) -> Option<&str> {
+        self.list.language()
+    }
+
+    fn source(&self) -> Option<&str> {
+        self.list.source()
+    }
+
+    fn dice_count(&self) -> Option<usize> {
+        self.list.dice_count()
+    }
+
+    fn dice_code(&self, index: usize) -> Option<String> {
+        self.list.dice_code(index)
+    }
+
+    fn lookup_dice(&self, code: &str) -> Option<&str> {
+        self.list.lookup_dice(code)
+    }
+
+    fn entropy_per_word(&self) -> f64 {
+        self.list.entropy_per_word()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::*;
+    use crate::ORIGINAL;
+
+    fn recipe(args: &[&str]) -> Result<Recipe, Error> {
+        Recipe::from_args(&Args::parse_from(args))
+    }
+
+    fn unmet(result: Result<Recipe, Error>) -> (String, String) {
+        match result {
+            Err(Error::Rules(RulesError::Unsatisfiable { rule, reason })) => (rule, reason),
+            other => panic!("expected an unsatisfiable rule, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn options_without_rules() {
+        let recipe = recipe(&["sppg", "-q", "-S", "_"]).unwrap();
+
+        assert_eq!(recipe.separator, Some('_'));
+        assert_eq!(recipe.capitals, 1);
+        assert_eq!(recipe.inserts, [Insert::special_chars(None, 1)]);
+        assert_eq!(recipe.inserts[0].chars.len(), 36);
+        assert!(!recipe.may_reject());
+    }
+
+    #[test]
+    fn lengths_are_only_indexed_when_needed() {
+        let plain = recipe(&["sppg"]).unwrap();
+        let words = plain.words(&ORIGINAL).unwrap();
+        plain.check_length(&words, 5).unwrap();
+        assert!(words.index.get().is_none());
+
+        let capped = recipe(&["sppg", "--max-length", "40"]).unwrap();
+        let words = capped.words(&ORIGINAL).unwrap();
+        capped.check_length(&words, 5).unwrap();
+        assert!(words.index.get().is_some());
+    }
+
+    #[test]
+    fn lists_need_two_words() {
+        let recipe = recipe(&["sppg"]).unwrap();
+        for len in [0, 1] {
+            let list: Vec<String> = vec!["only".into(); len];
+
+            assert!(matches!(
+                recipe.words(&list),
+                Err(Error::WordList(WordListError::TooShort { found })) if found == len
+            ));
+        }
+    }
+
+    #[test]
+    fn capitals_need_a_letter_with_case() {
+        let digits: Vec<String> = ["1", "42"].map(String::from).into();
+        let capital = recipe(&["sppg", "-c"]).unwrap();
+
+        assert!(matches!(
+            capital.words(&digits),
+            Err(Error::NoCapitals { .. })
+        ));
+        assert!(recipe(&["sppg"]).unwrap().words(&digits).is_ok());
+    }
+
+    #[test]
+    fn options_before_the_subcommand() {
+        let quality = recipe(&["sppg", "-q", "dice"]).unwrap();
+
+        assert_eq!(quality.capitals, 1);
+        assert_eq!(quality.inserts, [Insert::special_chars(None, 1)]);
+
+        let set = recipe(&["sppg", "--special-chars", "#!", "dice"]).unwrap();
+
+        assert_eq!(set.capitals, 0);
+        assert_eq!(set.inserts, [Insert::special_chars(Some("#!"), 1)]);
+    }
+
+    #[test]
+    fn rules_pick_the_separator_and_add_what_is_required() {
+        let recipe = recipe(&[
+            "sppg",
+            "--rules",
+            "required: lower; required: upper; required: digit; allowed: [-_.]",
+        ])
+        .unwrap();
+
+        assert_eq!(recipe.separator, Some('-'));
+        assert!(!recipe.upper_case);
+        assert_eq!(recipe.capitals, 1);
+        assert_eq!(recipe.inserts.len(), 1);
+        assert_eq!(recipe.inserts[0].what, "digit");
+        assert_eq!(recipe.inserts[0].chars, ('0'..='9').collect::<Vec<_>>());
+        assert!(recipe.may_reject());
+    }
+
+    #[test]
+    fn words_are_upper_case_without_lower_case_letters() {
+        let recipe = recipe(&["sppg", "--rules", "required: upper; allowed: digit, [-]"]).unwrap();
+
+        assert!(recipe.upper_case);
+        assert_eq!(recipe.capitals, 0, "every letter is already a capital");
+        assert!(recipe.inserts.is_empty());
+        assert_eq!(recipe.render("abc"), "ABC");
+    }
+
+    #[test]
+    fn special_chars_are_limited_to_those_allowed() {
+        // '-' is left to the special characters, as '_' can separate
+        let underscore = recipe(&["sppg", "-s", "--rules", "allowed: lower, [!#-_]"]).unwrap();
+
+        assert_eq!(underscore.inserts[0].chars, ['!', '#', '-']);
+        assert_eq!(underscore.separator, Some('_'));
+
+        let dash = recipe(&["sppg", "-s", "--rules", "allowed: lower, [!#-]"]).unwrap();
+        assert_eq!(dash.inserts[0].chars, ['!', '#']);
+        assert_eq!(dash.separator, Some('-'));
+    }
+
+    #[test]
+    fn special_chars_come_from_the_set_chosen() {
+        let digits = recipe(&["sppg", "--special-chars", "digits-only"]).unwrap();
+
+        assert_eq!(digits.inserts[0].chars, ('0'..='9').collect::<Vec<_>>());
+        assert_eq!(digits.inserts[0].entropy(), 10f64.log2());
+
+        let literal = recipe(&["sppg", "--special-chars", "@@#"]).unwrap();
+        assert_eq!(literal.inserts[0].chars, ['@', '#']);
+    }
+
+    #[test]
+    fn counts_replace_c_and_s() {
+        let counts = recipe(&["sppg", "-q", "--capitals", "3", "--specials", "2"]).unwrap();
+
+        assert_eq!(counts.capitals, 3);
+        assert_eq!(counts.inserts[0].count, 2);
+        assert_eq!(counts.insert_count(), 2);
+
+        let none = recipe(&["sppg", "-q", "--capitals", "0", "--specials", "0"]).unwrap();
+        assert_eq!(none.capitals, 0);
+        assert!(none.inserts.is_empty());
+    }
+
+    #[test]
+    fn digits_are_left_to_digits() {
+        let digits = recipe(&["sppg", "-s", "--digits", "2"]).unwrap();
+
+        assert_eq!(digits.inserts[0], Insert::digits(2));
+        assert_eq!(digits.inserts[1].chars.len(), 26);
+        assert!(!digits.inserts[1].chars.iter().any(char::is_ascii_digit));
+
+        assert!(matches!(
+            recipe(&["sppg", "--special-chars", "digits-only", "--digits", "1"]),
+            Err(Error::SharedChars {
+                what: "special character",
+                other: "digit"
+            })
+        ));
+    }
+
+    #[test]
+    fn separator_is_not_a_special_char() {
+        let dash = recipe(&["sppg", "-S", "-", "--special-chars=-!"]).unwrap();
+
+        assert_eq!(dash.inserts[0].chars, ['!']);
+
+        let camel = recipe(&["sppg", "--case", "camel", "--special-chars=-!"]).unwrap();
+        assert_eq!(camel.inserts[0].chars, ['-', '!']);
+
+        assert!(matches!(
+            recipe(&["sppg", "-S", "-", "--special-chars=-"]),
+            Err(Error::SharedChars {
+                what: "special character",
+                other: "separator"
+            })
+        ));
+    }
+
+    #[test]
+    fn required_digit_is_met_by_digits() {
+        let digits = recipe(&[
+            "sppg",
+            "--digits",
+            "1",
+            "--rules",
+            "required: digit; allowed: ascii-printable",
+        ])
+        .unwrap();
+
+        assert_eq!(digits.inserts, [Insert::digits(1)]);
+
+        let rule = recipe(&[
+            "sppg",
+            "-s",
+            "--rules",
+            "required: digit; allowed: ascii-printable",
+        ])
+        .unwrap();
+        assert_eq!(rule.inserts[0].what, "digit");
+        assert_eq!(rule.inserts[1].chars.len(), 26);
+    }
+
+    #[test]
+    fn camel_case_has_no_separators() {
+        let camel = recipe(&["sppg", "--case", "camel"]).unwrap();
+        let words = camel.words(&ORIGINAL).unwrap();
+
+        assert_eq!(camel.reserved(&words, 4), 0);
+        assert_eq!(camel.passphrase().push("Ab").push("Cd").to_string(), "AbCd");
+        assert_eq!(camel.longest_form("ab"), 2);
+    }
+
+    #[test]
+    fn case_styles_need_capitals_allowed() {
+        let (_, reason) = unmet(recipe(&[
+            "sppg",
+            "--case",
+            "title",
+            "--rules",
+            "allowed: lower, [-]",
+        ]));
+        assert_eq!(reason, "--case adds capital letters, which aren't allowed");
+
+        let title = recipe(&[
+            "sppg",
+            "--case",
+            "title",
+            "--rules",
+            "required: upper; required: lower; allowed: [-]",
+        ])
+        .unwrap();
+        assert_eq!(title.capitals, 0, "every phrase already has a capital");
+
+        let upper =
+            recipe(&["sppg", "--case", "random", "--rules", "allowed: upper, [-]"]).unwrap();
+        assert!(upper.upper_case);
+        assert_eq!(upper.case, Case::Lower);
+    }
+
+    #[test]
+    fn required_special_is_met_by_the_separator() {
+        let recipe = recipe(&["sppg", "--rules", "required: lower; required: special"]).unwrap();
+
+        assert_eq!(recipe.separator, Some(' '));
+        assert!(recipe.inserts.is_empty());
+    }
+
+    #[test]
+    fn unsatisfiable_rules_are_named() {
+        let (rule, reason) = unmet(recipe(&[
+            "sppg",
+            "-S",
+            "_",
+            "--rules",
+            "allowed: lower, [-]",
+        ]));
+        assert_eq!(rule, "allowed: lower, [-]");
+        assert_eq!(reason, "the separator '_' isn't allowed");
+
+        let (rule, _) = unmet(recipe(&["sppg", "--rules", "allowed: lower"]));
+        assert_eq!(rule, "allowed: lower");
+
+        let (_, reason) = unmet(recipe(&["sppg", "--rules", "allowed: digit, [-]"]));
+        assert_eq!(reason, "the words need letters, which aren't allowed");
+
+        let (_, reason) = unmet(recipe(&["sppg", "-c", "--rules", "allowed: lower, [-]"]));
+        assert_eq!(reason, "-c adds a capital letter, which isn't allowed");
+    }
+
+    #[test]
+    fn words_are_those_allowed() {
+        let recipe = recipe(&["sppg", "--rules", "allowed: lower, [ ]"]).unwrap();
+        let list = recipe.words(&ORIGINAL).unwrap();
+
+        assert!(list.len() < ORIGINAL.len());
+        assert!((0..list.len()).all(|index| list
+            .word(index)
+            .unwrap()
+            .chars()
+            .all(|c| c.is_ascii_lowercase())));
+
+        let recipe = Recipe::from_args(&Args::parse_from(["sppg"])).unwrap();
+        assert_eq!(recipe.words(&ORIGINAL).unwrap().len(), ORIGINAL.len());
+    }
+
+    #[test]
+    fn lengths_are_checked_against_the_words() {
+        // Words of 1 to 6 letters, and a digit added to one of them
+        let list: Vec<String> = (1..=6).map(|len| "x".repeat(len)).collect();
+        let recipe = recipe(&[
+            "sppg",
+            "--rules",
+            "minlength: 12; maxlength: 12; required: digit; allowed: lower, [ ]",
+        ])
+        .unwrap();
+        let words = recipe.words(&list).unwrap();
+
+        assert_eq!(recipe.max_length, Some(12));
+        // 3 words are 6 to 21 characters
+        assert!(recipe.check_length(&words, 3).is_ok());
+        // 6 words are at least 12
+        assert!(recipe.check_length(&words, 6).is_ok());
+        assert_eq!(
+            recipe.check_length(&words, 7).unwrap_err().to_string(),
+            "password rule 'maxlength: 12' can't be met: the shortest phrase of 7 words is 14 characters"
+        );
+        // 1 word is at most 7
+        assert_eq!(
+            recipe.check_length(&words, 1).unwrap_err().to_string(),
+            "password rule 'minlength: 12' can't be met: the longest phrase of 1 words is 7 characters; use more words"
+        );
+    }
+
+    #[test]
+    fn max_length_is_the_lower_of_option_and_rule() {
+        let lower = recipe(&["sppg", "--max-length", "20", "--rules", "maxlength: 32"]).unwrap();
+        let higher = recipe(&["sppg", "--max-length", "40", "--rules", "maxlength: 32"]).unwrap();
+
+        assert_eq!(lower.max_length, Some(20));
+        assert_eq!(higher.max_length, Some(32));
+    }
+
+    #[test]
+    fn room_is_what_the_words_may_take_up() {
+        // 3 separators, and a special char that may bring a fourth
+        let recipe = recipe(&[
+            "sppg",
+            "-s",
+            "--special-placement",
+            "any",
+            "--max-length",
+            "20",
+        ])
+        .unwrap();
+        let words = recipe.words(&ORIGINAL).unwrap();
+
+        assert_eq!(recipe.reserved(&words, 4), 5);
+        assert_eq!(recipe.room(&words, 4), Some(15));
+        // Leaving 3 of the 1 letter words
+        assert_eq!(recipe.word_limit(&words, 15, 3), 12);
+        assert_eq!(
+            words.fitting(1),
+            (0..7776)
+                .filter(|i| ORIGINAL.word(*i).unwrap().len() == 1)
+                .count()
+        );
+        assert_eq!(words.fitting(words.longest()), 7776);
+    }
+
+    #[test]
+    fn capital_growth_is_reserved() {
+        let list: Vec<String> = ["straße", "weg"].map(String::from).into();
+        let recipe = recipe(&["sppg", "-c", "--max-length", "10"]).unwrap();
+        let words = recipe.words(&list).unwrap();
+
+        assert_eq!(recipe.reserved(&words, 2), 2);
+        assert_eq!(
+            recipe.check_max_length(&words, 4).unwrap_err().to_string(),
+            "--max-length 10 is too short: the shortest phrase of 4 words is 16 characters"
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+// Any source of randomness that is fit for making secrets. Every function
+// that makes part of a passphrase takes one, so that a caller can use a
+// hardware source, or a seeded one in tests.
+pub trait SecureRng: RngCore + CryptoRng {}
+
+impl<R: RngCore + CryptoRng> SecureRng for R {}
+
+// The sources that can be chosen with --rng
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RngSource {
+    // A generator local to the thread, seeded from the OS and reseeded
+    // regularly
+    #[default]
+    Thread,
+    // The OS source itself, asked for every number
+    Os,
+}
+
+pub fn rng_for(source: RngSource) -> Box<dyn SecureRng> {
+    match source {
+        RngSource::Thread => Box::new(rand::thread_rng()),
+        RngSource::Os => Box::new(OsRng),
+    }
+}
+
+// The generator used by --seed: ChaCha20 keyed from the seed as described
+// by SeedableRng::seed_from_u64(). The same seed always gives the same
+// numbers, on every platform, which makes it useless for real secrets.
+pub fn seeded_rng(seed: u64) -> ChaCha20Rng {
+    ChaCha20Rng::seed_from_u64(seed)
+}
@@ -0,0 +1,351 @@
+use std::{collections::BTreeSet, fmt};
+
+// Apple's "special" class: ASCII punctuation, less '/' and '\', and space
+const SPECIAL: &str = "-~!@#$%^&*_+=`|(){}[:;\"'<>,.? ]";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RulesError {
+    // A property that couldn't be read
+    Parse { rule: String, reason: String },
+    // A property that no phrase made with the other options can meet
+    Unsatisfiable { rule: String, reason: String },
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Parse { rule, reason } => {
+                write!(f, "invalid password rule '{rule}': {reason}")
+            }
+            RulesError::Unsatisfiable { rule, reason } => {
+                write!(f, "password rule '{rule}' can't be met: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+// A set of characters, as named by one or more classes of the
+// passwordrules language
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CharClass {
+    chars: BTreeSet<char>,
+    // The "unicode" class, which holds every character
+    unicode: bool,
+}
+
+impl CharClass {
+    pub fn contains(&self, ch: char) -> bool {
+        self.unicode || self.chars.contains(&ch)
+    }
+
+    pub fn contains_all<I: IntoIterator<Item = char>>(&self, chars: I) -> bool {
+        chars.into_iter().all(|ch| self.contains(ch))
+    }
+
+    pub fn is_unicode(&self) -> bool {
+        self.unicode
+    }
+
+    // The characters of the class in code point order. The "unicode" class
+    // is taken to be the ASCII printable ones.
+    pub fn chars(&self) -> Vec<char> {
+        if self.unicode {
+            return (' '..='~').collect();
+        }
+
+        self.chars.iter().copied().collect()
+    }
+
+    fn union(&mut self, other: &CharClass) {
+        self.chars.extend(&other.chars);
+        self.unicode |= other.unicode;
+    }
+}
+
+// The requirements of a site, parsed from its passwordrules, e.g.
+// "minlength: 12; required: upper; required: digit; allowed: [-_.]"
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rules {
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub max_consecutive: Option<usize>,
+    // Each phrase needs at least one character of each class, which is
+    // kept with the text of its property so that errors can name it
+    pub required: Vec<(String, CharClass)>,
+    allowed: Vec<(String, CharClass)>,
+}
+
+impl Rules {
+    // Every character a phrase may have: those of the required and allowed
+    // classes, or the ASCII printable ones if there are none
+    pub fn allowed(&self) -> CharClass {
+        let mut allowed = CharClass::default();
+        for (_, class) in self.required.iter().chain(&self.allowed) {
+            allowed.union(class);
+        }
+        if self.required.is_empty() && self.allowed.is_empty() {
+            allowed.chars.extend(' '..='~');
+        }
+
+        allowed
+    }
+
+    // The properties that make up allowed(), to name in errors
+    pub fn allowed_rule(&self) -> String {
+        let rules: Vec<&str> = self
+            .required
+            .iter()
+            .chain(&self.allowed)
+            .map(|(rule, _)| rule.as_str())
+            .collect();
+        if rules.is_empty() {
+            return "allowed: ascii-printable".into();
+        }
+
+        rules.join("; ")
+    }
+
+    // The first property `phrase` breaks, if any
+    pub fn broken_by(&self, phrase: &str) -> Option<String> {
+        let len = phrase.chars().count();
+        if let Some(min) = self.min_length.filter(|min| len < *min) {
+            return Some(format!("minlength: {min}"));
+        }
+        if let Some(max) = self.max_length.filter(|max| len > *max) {
+            return Some(format!("maxlength: {max}"));
+        }
+        if let Some(max) = self.max_consecutive {
+            if longest_run(phrase) > max {
+                return Some(format!("max-consecutive: {max}"));
+            }
+        }
+        for (rule, class) in &self.required {
+            if !phrase.chars().any(|ch| class.contains(ch)) {
+                return Some(rule.clone());
+            }
+        }
+        let allowed = self.allowed();
+        if !phrase.chars().all(|ch| allowed.contains(ch)) {
+            return Some(self.allowed_rule());
+        }
+
+        None
+    }
+
+    pub fn accepts(&self, phrase: &str) -> bool {
+        self.broken_by(phrase).is_none()
+    }
+}
+
+// The most times one character is repeated in a row
+fn longest_run(phrase: &str) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut last = None;
+    for ch in phrase.chars() {
+        run = if last == Some(ch) { run + 1 } else { 1 };
+        longest = longest.max(run);
+        last = Some(ch);
+    }
+
+    longest
+}
+
+// Reads rules in the passwordrules language. Properties are separated by
+// ';' and their names are not case sensitive; those this parser doesn't
+// know are ignored, as the language asks.
+pub fn parse_rules(text: &str) -> Result<Rules, RulesError> {
+    let mut rules = Rules::default();
+    for property in split_outside_brackets(text, ';') {
+        let property = property.trim();
+        if property.is_empty() {
+            continue;
+        }
+        let error = |reason: &str| RulesError::Parse {
+            rule: property.into(),
+            reason: reason.into(),
+        };
+        let (name, value) = property
+            .split_once(':')
+            .ok_or_else(|| error("expected 'name: value'"))?;
+        let value = value.trim();
+        let number = || value.parse::<usize>().map_err(|_| error("not a number"));
+        match name.trim().to_ascii_lowercase().as_str() {
+            "minlength" => rules.min_length = Some(number()?),
+            "maxlength" => rules.max_length = Some(number()?),
+            "max-consecutive" => rules.max_consecutive = Some(number()?),
+            "required" => rules.required.push((
+                property.into(),
+                parse_classes(value).map_err(|e| error(&e))?,
+            )),
+            "allowed" => rules.allowed.push((
+                property.into(),
+                parse_classes(value).map_err(|e| error(&e))?,
+            )),
+            _ => {}
+        }
+    }
+
+    if let (Some(min), Some(max)) = (rules.min_length, rules.max_length) {
+        if min > max {
+            return Err(RulesError::Unsatisfiable {
+                rule: format!("minlength: {min}"),
+                reason: format!("it is longer than maxlength: {max}"),
+            });
+        }
+    }
+
+    Ok(rules)
+}
+
+// The union of a comma separated list of classes, e.g. "upper, [-_.]"
+fn parse_classes(value: &str) -> Result<CharClass, String> {
+    let mut class = CharClass::default();
+    for name in split_outside_brackets(value, ',') {
+        let name = name.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "upper" => class.chars.extend('A'..='Z'),
+            "lower" => class.chars.extend('a'..='z'),
+            "digit" => class.chars.extend('0'..='9'),
+            "special" => class.chars.extend(SPECIAL.chars()),
+            "ascii-printable" => class.chars.extend(' '..='~'),
+            "unicode" => class.unicode = true,
+            "" => return Err("expected a character class".into()),
+            _ => match name.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+                Some(chars) if !chars.is_empty() => class.chars.extend(chars.chars()),
+                _ => return Err(format!("unknown character class '{name}'")),
+            },
+        }
+    }
+
+    Ok(class)
+}
+
+// Splits `text` on `delimiter`, except inside a custom class such as
+// "[;,]". A ']' right after the '[' is part of the class.
+fn split_outside_brackets(text: &str, delimiter: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut open = None;
+    for (i, ch) in text.char_indices() {
+        match open {
+            Some(at) if ch == ']' && i > at + 1 => open = None,
+            Some(_) => {}
+            None if ch == '[' => open = Some(i),
+            None if ch == delimiter => {
+                parts.push(&text[start..i]);
+                start = i + ch.len_utf8();
+            }
+            None => {}
+        }
+    }
+    parts.push(&text[start..]);
+
+    parts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_every_property() {
+        let rules = parse_rules(
+            "minlength: 12; maxlength: 32; max-consecutive: 2; required: upper; required: digit; allowed: [-_.]",
+        )
+        .unwrap();
+
+        assert_eq!(rules.min_length, Some(12));
+        assert_eq!(rules.max_length, Some(32));
+        assert_eq!(rules.max_consecutive, Some(2));
+        assert_eq!(rules.required.len(), 2);
+        assert_eq!(rules.required[1].0, "required: digit");
+        let allowed = rules.allowed();
+        assert!(allowed.contains_all(['A', 'Z', '0', '9', '-', '_', '.']));
+        assert!(!allowed.contains('a'));
+        assert!(!allowed.contains(' '));
+    }
+
+    #[test]
+    fn everything_printable_is_allowed_by_default() {
+        let rules = parse_rules("minlength: 8").unwrap();
+
+        assert_eq!(rules.allowed().chars(), (' '..='~').collect::<Vec<_>>());
+        assert_eq!(rules.allowed_rule(), "allowed: ascii-printable");
+    }
+
+    #[test]
+    fn parse_classes_and_custom_sets() {
+        let rules = parse_rules("Allowed: lower, [;,]]; required: special").unwrap();
+        let allowed = rules.allowed();
+
+        assert!(allowed.contains_all(['a', ';', ',', ']', '?', ' ']));
+        assert!(!allowed.contains('A'));
+        assert!(!allowed.contains('/'));
+    }
+
+    #[test]
+    fn unknown_properties_are_ignored() {
+        assert_eq!(
+            parse_rules("passwordlength: 5;;").unwrap(),
+            Rules::default()
+        );
+    }
+
+    #[test]
+    fn parse_errors_name_the_property() {
+        assert_eq!(
+            parse_rules("minlength: twelve").unwrap_err(),
+            RulesError::Parse {
+                rule: "minlength: twelve".into(),
+                reason: "not a number".into()
+            }
+        );
+        assert_eq!(
+            parse_rules("required: uppr").unwrap_err().to_string(),
+            "invalid password rule 'required: uppr': unknown character class 'uppr'"
+        );
+        assert!(parse_rules("required").is_err());
+        assert!(parse_rules("allowed: lower,").is_err());
+        assert!(parse_rules("allowed: []").is_err());
+    }
+
+    #[test]
+    fn minlength_past_maxlength_is_unsatisfiable() {
+        assert_eq!(
+            parse_rules("minlength: 20; maxlength: 10").unwrap_err(),
+            RulesError::Unsatisfiable {
+                rule: "minlength: 20".into(),
+                reason: "it is longer than maxlength: 10".into()
+            }
+        );
+    }
+
+    #[test]
+    fn phrases_are_checked_against_each_property() {
+        let rules =
+            parse_rules("minlength: 10; maxlength: 16; max-consecutive: 2; required: digit; allowed: lower, [-]")
+                .unwrap();
+
+        assert_eq!(rules.broken_by("abc-def-7gh"), None);
+        assert_eq!(rules.broken_by("ab-7"), Some("minlength: 10".into()));
+        assert_eq!(
+            rules.broken_by("abcdef-ghijk-lmn7"),
+            Some("maxlength: 16".into())
+        );
+        assert_eq!(
+            rules.broken_by("abc-dddef-7gh"),
+            Some("max-consecutive: 2".into())
+        );
+        assert_eq!(
+            rules.broken_by("abc-def-ghi"),
+            Some("required: digit".into())
+        );
+        assert_eq!(
+            rules.broken_by("abc def 7gh"),
+            Some("required: digit; allowed: lower, [-]".into())
+        );
+    }
+}
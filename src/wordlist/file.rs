@@ -0,0 +1,394 @@
+use std::{collections::HashSet, fmt, fs, io, path::Path};
+
+use super::{WordList, BUILTIN_LISTS};
+
+// Reinhold's list is distributed as a PGP clear-signed message
+const PGP_MESSAGE_BEGIN: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const PGP_SIGNATURE_BEGIN: &str = "-----BEGIN PGP SIGNATURE-----";
+
+#[derive(Debug)]
+pub enum WordListError {
+    Io(io::Error),
+    Parse { line: usize, reason: String },
+    Length { found: usize, expected: usize },
+    TooShort { found: usize },
+    Unknown(String),
+}
+
+impl fmt::Display for WordListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WordListError::Io(e) => write!(f, "unable to read word list: {e}"),
+            WordListError::Parse { line, reason } => write!(f, "line {line}: {reason}"),
+            WordListError::Length { found, expected } => {
+                write!(f, "word list has {found} words, expected {expected}")
+            }
+            WordListError::TooShort { found } => {
+                write!(f, "word list has {found} words, expected at least 2")
+            }
+            WordListError::Unknown(name) => {
+                let names: Vec<&str> = BUILTIN_LISTS.iter().map(|l| l.name()).collect();
+                write!(
+                    f,
+                    "no built-in word list named '{name}' (available: {})",
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for WordListError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WordListError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WordListError {
+    fn from(e: io::Error) -> Self {
+        WordListError::Io(e)
+    }
+}
+
+// A list read from disk, named after the file it came from
+#[derive(Clone, Debug)]
+pub struct FileWordList {
+    name: String,
+    words: Vec<String>,
+}
+
+impl WordList for FileWordList {
+    fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    fn word(&self, index: usize) -> Option<&str> {
+        self.words.word(index)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+pub fn load_word_list(path: &Path) -> Result<FileWordList, WordListError> {
+    let contents = fs::read_to_string(path)?;
+    let words = parse_word_list(&contents)?;
+    let name = path.file_stem().map_or_else(
+        || path.display().to_string(),
+        |s| s.to_string_lossy().into(),
+    );
+
+    Ok(FileWordList { name, words })
+}
+
+// Accepts the PGP-armored diceware.wordlist.asc layout, the tab separated
+// EFF layout, and a plain list with one word per line. The words are
+// returned in dice order. Plain lists may be of any length and are kept
+// in the order the words appear.
+pub fn parse_word_list(contents: &str) -> Result<Vec<String>, WordListError> {
+    let mut keyed = Vec::<(usize, String, &str)>::new();
+    let mut plain = Vec::<(usize, &str)>::new();
+    let mut in_pgp_header = false;
+    for (idx, line) in contents.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+        if line == PGP_MESSAGE_BEGIN {
+            in_pgp_header = true;
+            continue;
+        }
+        if in_pgp_header {
+            // The armor headers end at the first blank line
+            if line.is_empty() {
+                in_pgp_header = false;
+            }
+            continue;
+        }
+        if line == PGP_SIGNATURE_BEGIN {
+            break;
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        // Clear-signed messages dash-escape lines that start with a '-'
+        let line = line.strip_prefix("- ").unwrap_or(line);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            [code, word] if plain.is_empty() => keyed.push((line_no, code.into(), word)),
+            [word] if keyed.is_empty() => plain.push((line_no, word)),
+            [_, _] | [_] => {
+                return Err(WordListError::Parse {
+                    line: line_no,
+                    reason: "mixes plain and dice-numbered entries".into(),
+                })
+            }
+            _ => {
+                return Err(WordListError::Parse {
+                    line: line_no,
+                    reason: format!("expected a dice code and a word, found '{line}'"),
+                })
+            }
+        }
+    }
+
+    if keyed.is_empty() {
+        return validate_plain(plain);
+    }
+
+    validate_keyed(keyed)
+}
+
+fn validate_plain(entries: Vec<(usize, &str)>) -> Result<Vec<String>, WordListError> {
+    let mut words = HashSet::<&str>::new();
+    for (line_no, word) in &entries {
+        check_unique_word(&mut words, *line_no, word)?;
+    }
+    if entries.len() < 2 {
+        return Err(WordListError::TooShort {
+            found: entries.len(),
+        });
+    }
+
+    Ok(entries.into_iter().map(|(_, word)| word.into()).collect())
+}
+
+fn validate_keyed(mut entries: Vec<(usize, String, &str)>) -> Result<Vec<String>, WordListError> {
+    // Every code in the list has as many digits as the first one
+    let dice_count = entries.first().map_or(0, |(_, code, _)| code.len());
+    let mut codes = HashSet::<&str>::new();
+    let mut words = HashSet::<&str>::new();
+    for (line_no, code, word) in &entries {
+        let is_dice_roll =
+            code.len() == dice_count && code.bytes().all(|b| (b'1'..=b'6').contains(&b));
+        if !is_dice_roll {
+            return Err(WordListError::Parse {
+                line: *line_no,
+                reason: format!("invalid dice code '{code}'"),
+            });
+        }
+        if !codes.insert(code) {
+            return Err(WordListError::Parse {
+                line: *line_no,
+                reason: format!("duplicate dice code '{code}'"),
+            });
+        }
+        check_unique_word(&mut words, *line_no, word)?;
+    }
+
+    // A code can have more digits than a usize can count rolls of
+    let expected = u32::try_from(dice_count)
+        .ok()
+        .and_then(|n| 6usize.checked_pow(n))
+        .ok_or_else(|| WordListError::Parse {
+            line: entries[0].0,
+            reason: format!("dice code '{}' is too long", entries[0].1),
+        })?;
+    if entries.len() != expected {
+        return Err(WordListError::Length {
+            found: entries.len(),
+            expected,
+        });
+    }
+
+    // Codes of equal length sort in the same order as the rolls they name
+    entries.sort_by(|a, b| a.1.cmp(&b.1));
+
+    Ok(entries
+        .into_iter()
+        .map(|(_, _, word)| word.into())
+        .collect())
+}
+
+fn check_unique_word<'a>(
+    words: &mut HashSet<&'a str>,
+    line_no: usize,
+    word: &'a str,
+) -> Result<(), WordListError> {
+    if !words.insert(word) {
+        return Err(WordListError::Parse {
+            line: line_no,
+            reason: format!("duplicate word '{word}'"),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wordlist::dice_code;
+
+    const LIST_LEN: usize = 7776;
+
+    fn plain_list(len: usize) -> String {
+        (0..len).map(|i| format!("word{i}\n")).collect()
+    }
+
+    #[test]
+    fn parse_eff_layout() {
+        let contents = include_str!("../../wordlists/eff_large_wordlist.txt");
+        let list = parse_word_list(contents).expect("the EFF list parses");
+
+        assert_eq!(list.len(), LIST_LEN);
+        assert_eq!(list[0], "abacus");
+        assert_eq!(list[LIST_LEN - 1], "zoom");
+    }
+
+    #[test]
+    fn parse_pgp_armored_layout() {
+        let body = include_str!("../../wordlists/diceware.wordlist.asc");
+        let contents = format!(
+            "{PGP_MESSAGE_BEGIN}\nHash: SHA1\n\n{body}\n{PGP_SIGNATURE_BEGIN}\nVersion: 2.6.2\n\niQCVAwUBNtzE\n-----END PGP SIGNATURE-----\n"
+        );
+        let list = parse_word_list(&contents).expect("the armored list parses");
+
+        assert_eq!(list.len(), LIST_LEN);
+        assert_eq!(list[0], "a");
+        assert_eq!(list[LIST_LEN - 1], "@");
+    }
+
+    #[test]
+    fn parse_dash_escaped_line() {
+        let contents =
+            include_str!("../../wordlists/diceware.wordlist.asc").replace("66656\t-", "- 66656\t-");
+        let list = parse_word_list(&contents).expect("the dash escaped list parses");
+
+        assert_eq!(list[7769], "-", "66656 is the 7770th word");
+    }
+
+    #[test]
+    fn parse_plain_layout() {
+        let list = parse_word_list(&plain_list(LIST_LEN)).expect("the plain list parses");
+
+        assert_eq!(list[0], "word0");
+        assert_eq!(list[7], "word7");
+        assert_eq!(list[LIST_LEN - 1], "word7775");
+    }
+
+    #[test]
+    fn parse_keyed_layout_out_of_order() {
+        let contents: String = (0..1296)
+            .rev()
+            .map(|i| format!("{}\tword{i}\n", dice_code(i, 4)))
+            .collect();
+        let list = parse_word_list(&contents).expect("the list parses");
+
+        assert_eq!(list[0], "word0", "words are returned in dice order");
+        assert_eq!(list[1295], "word1295");
+    }
+
+    #[test]
+    fn parse_four_dice_layout() {
+        let contents: String = (0..1296)
+            .map(|i| format!("{}\tword{i}\n", dice_code(i, 4)))
+            .collect();
+        let list = parse_word_list(&contents).expect("the four dice list parses");
+
+        assert_eq!(list.len(), 1296);
+        assert_eq!(list[0], "word0");
+        assert_eq!(list[1295], "word1295");
+    }
+
+    #[test]
+    fn parse_plain_layout_of_any_length() {
+        for len in [2, 1296, 2048, 5000] {
+            let list = parse_word_list(&plain_list(len)).expect("the plain list parses");
+
+            assert_eq!(list.len(), len);
+            assert_eq!(list[len - 1], format!("word{}", len - 1));
+        }
+    }
+
+    #[test]
+    fn error_names_line_of_mismatched_dice_count() {
+        let contents = include_str!("../../wordlists/eff_large_wordlist.txt").replacen(
+            "11113\tabdominal",
+            "1113\tabdominal",
+            1,
+        );
+        let err = parse_word_list(&contents).expect_err("the dice code is too short");
+
+        assert_eq!(err.to_string(), "line 3: invalid dice code '1113'");
+    }
+
+    #[test]
+    fn error_names_line_of_bad_dice_code() {
+        let contents = include_str!("../../wordlists/eff_large_wordlist.txt").replacen(
+            "11113\tabdominal",
+            "11173\tabdominal",
+            1,
+        );
+        let err = parse_word_list(&contents).expect_err("the dice code is invalid");
+
+        assert_eq!(err.to_string(), "line 3: invalid dice code '11173'");
+    }
+
+    #[test]
+    fn error_names_line_of_overlong_dice_code() {
+        let code = "1".repeat(25);
+        let contents = format!("{code}\tone\n{}2\ttwo\n", &code[1..]);
+        let err = parse_word_list(&contents).expect_err("the dice code is too long");
+
+        assert_eq!(
+            err.to_string(),
+            format!("line 1: dice code '{code}' is too long")
+        );
+    }
+
+    #[test]
+    fn error_names_line_of_duplicate_word() {
+        let contents = include_str!("../../wordlists/eff_large_wordlist.txt").replacen(
+            "11113\tabdominal",
+            "11113\tabacus",
+            1,
+        );
+        let err = parse_word_list(&contents).expect_err("the word is a duplicate");
+
+        assert_eq!(err.to_string(), "line 3: duplicate word 'abacus'");
+    }
+
+    #[test]
+    fn error_names_line_of_mixed_entries() {
+        let contents = plain_list(LIST_LEN).replacen("word4\n", "11115\tword4\n", 1);
+        let err = parse_word_list(&contents).expect_err("the list mixes layouts");
+
+        assert_eq!(
+            err.to_string(),
+            "line 5: mixes plain and dice-numbered entries"
+        );
+    }
+
+    #[test]
+    fn error_on_short_list() {
+        let contents = include_str!("../../wordlists/eff_large_wordlist.txt").replacen(
+            "11113\tabdominal\n",
+            "",
+            1,
+        );
+        let err = parse_word_list(&contents).expect_err("the list is too short");
+
+        assert_eq!(err.to_string(), "word list has 7775 words, expected 7776");
+    }
+
+    #[test]
+    fn error_on_plain_list_of_one_word() {
+        let err = parse_word_list("one\n").expect_err("the list is too short");
+
+        assert_eq!(
+            err.to_string(),
+            "word list has 1 words, expected at least 2"
+        );
+    }
+
+    #[test]
+    fn error_names_line_of_duplicate_plain_word() {
+        let err = parse_word_list("one\ntwo\none\n").expect_err("the word is a duplicate");
+
+        assert_eq!(err.to_string(), "line 3: duplicate word 'one'");
+    }
+}
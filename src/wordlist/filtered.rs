@@ -0,0 +1,147 @@
+use super::{dice_index, WordList};
+
+// The words of another list that pass a test, in the same order. Each
+// word keeps the dice code it has in the other list, so a roll of a word
+// that was taken out picks nothing.
+#[derive(Debug)]
+pub struct FilteredList<'a, W: WordList + ?Sized> {
+    inner: &'a W,
+    // The index in `inner` of each word kept, or None if all of them are
+    indices: Option<Vec<usize>>,
+}
+
+impl<'a, W: WordList + ?Sized> FilteredList<'a, W> {
+    pub fn new<F: Fn(&str) -> bool>(inner: &'a W, keep: F) -> Self {
+        let indices: Vec<usize> = (0..inner.len())
+            .filter(|index| inner.word(*index).is_some_and(&keep))
+            .collect();
+        let indices = (indices.len() < inner.len()).then_some(indices);
+
+        Self { inner, indices }
+    }
+
+    // Every word of `inner`, without testing any of them
+    pub fn all(inner: &'a W) -> Self {
+        Self {
+            inner,
+            indices: None,
+        }
+    }
+
+    pub fn is_filtered(&self) -> bool {
+        self.indices.is_some()
+    }
+
+    // The index of the word at `index` in the other list
+    fn inner_index(&self, index: usize) -> Option<usize> {
+        match &self.indices {
+            Some(indices) => indices.get(index).copied(),
+            None => (index < self.inner.len()).then_some(index),
+        }
+    }
+
+    // The index in this list of the word at `inner_index` in the other
+    // one, or None if it was taken out
+    pub fn index_of(&self, inner_index: usize) -> Option<usize> {
+        match &self.indices {
+            Some(indices) => indices.binary_search(&inner_index).ok(),
+            None => (inner_index < self.inner.len()).then_some(inner_index),
+        }
+    }
+}
+
+impl<W: WordList + ?Sized> WordList for FilteredList<'_, W> {
+    fn len(&self) -> usize {
+        match &self.indices {
+            Some(indices) => indices.len(),
+            None => self.inner.len(),
+        }
+    }
+
+    fn word(&self, index: usize) -> Option<&str> {
+        self.inner.word(self.inner_index(index)?)
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn language(&self) -> Option<&str> {
+        self.inner.language()
+    }
+
+    fn source(&self) -> Option<&str> {
+        self.inner.source()
+    }
+
+    fn dice_count(&self) -> Option<usize> {
+        self.inner.dice_count()
+    }
+
+    fn dice_code(&self, index: usize) -> Option<String> {
+        self.inner.dice_code(self.inner_index(index)?)
+    }
+
+    fn lookup_dice(&self, code: &str) -> Option<&str> {
+        let inner_index = dice_index(code, self.dice_count()?)?;
+        self.word(self.index_of(inner_index)?)
+    }
+
+    // Fewer words than the other list, picked with equal chance
+    fn entropy_per_word(&self) -> f64 {
+        (self.len() as f64).log2()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ORIGINAL;
+
+    #[test]
+    fn unfiltered_list_keeps_its_dice() {
+        let list = FilteredList::new(&ORIGINAL, |_| true);
+
+        assert!(!list.is_filtered());
+        assert_eq!(list.len(), 7776);
+        assert_eq!(list.dice_count(), Some(5));
+        assert_eq!(list.lookup_dice("11111"), ORIGINAL.lookup_dice("11111"));
+    }
+
+    #[test]
+    fn filtered_list_keeps_matching_words_in_order() {
+        let words: Vec<String> = ["ab", "c1", "de", "f!"].map(String::from).into();
+        let list = FilteredList::new(&words, |w| w.chars().all(char::is_alphabetic));
+
+        assert!(list.is_filtered());
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.word(0), Some("ab"));
+        assert_eq!(list.word(1), Some("de"));
+        assert_eq!(list.word(2), None);
+        assert_eq!(list.dice_count(), None);
+        assert_eq!(list.dice_code(0), None);
+    }
+
+    #[test]
+    fn filtered_list_keeps_the_dice_codes_of_its_words() {
+        let list = FilteredList::new(&ORIGINAL, |w| w.chars().all(char::is_alphabetic));
+
+        assert!(list.is_filtered());
+        assert_eq!(list.dice_count(), Some(5));
+        assert_eq!(list.word(0), Some("a"));
+        assert_eq!(list.dice_code(0).as_deref(), Some("11111"));
+        // "a&p" and "a's" are taken out
+        assert_eq!(list.lookup_dice("11112"), None);
+        assert_eq!(list.index_of(1), None);
+        assert_eq!(list.dice_code(1).as_deref(), Some("11114"));
+        for index in [1, 2, 100, list.len() - 1] {
+            let code = list.dice_code(index).unwrap();
+            assert_eq!(
+                list.lookup_dice(&code),
+                list.word(index),
+                "dice code {code}"
+            );
+            assert_eq!(ORIGINAL.lookup_dice(&code), list.word(index));
+        }
+    }
+}
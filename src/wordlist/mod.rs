@@ -1,3 +1,311 @@
+use rand::{
+    distributions::{Distribution, Uniform},
+    Rng,
+};
+
 pub mod eff;
+pub mod file;
+pub mod filtered;
 pub mod original;
 pub mod special_char;
+
+// A list of words addressed by index. Lists with 6^n words can also be
+// addressed by the roll of n dice, in which case the words are kept in
+// dice order ("11111" is the first word, "11112" the second, ...).
+pub trait WordList {
+    fn len(&self) -> usize;
+
+    fn word(&self, index: usize) -> Option<&str>;
+
+    fn name(&self) -> &str;
+
+    // An IETF language tag, if the language of the list is known
+    fn language(&self) -> Option<&str> {
+        None
+    }
+
+    // Where the list was published, if known
+    fn source(&self) -> Option<&str> {
+        None
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The number of dice in a roll, or None if the list can't be used
+    // with dice
+    fn dice_count(&self) -> Option<usize> {
+        dice_count_for(self.len())
+    }
+
+    fn dice_code(&self, index: usize) -> Option<String> {
+        let dice_count = self.dice_count()?;
+        (index < self.len()).then(|| dice_code(index, dice_count))
+    }
+
+    fn lookup_dice(&self, code: &str) -> Option<&str> {
+        self.word(dice_index(code, self.dice_count()?)?)
+    }
+
+    fn entropy_per_word(&self) -> f64 {
+        (self.len() as f64).log2()
+    }
+}
+
+impl<W: WordList + ?Sized> WordList for &W {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn word(&self, index: usize) -> Option<&str> {
+        (**self).word(index)
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn language(&self) -> Option<&str> {
+        (**self).language()
+    }
+
+    fn source(&self) -> Option<&str> {
+        (**self).source()
+    }
+
+    fn dice_count(&self) -> Option<usize> {
+        (**self).dice_count()
+    }
+
+    fn dice_code(&self, index: usize) -> Option<String> {
+        (**self).dice_code(index)
+    }
+
+    fn lookup_dice(&self, code: &str) -> Option<&str> {
+        (**self).lookup_dice(code)
+    }
+
+    fn entropy_per_word(&self) -> f64 {
+        (**self).entropy_per_word()
+    }
+}
+
+// Uniform rejects the samples that would favour the lower indices, so
+// every word is equally likely whatever the length of the list. The index
+// is drawn as a u32, as the samples drawn for a usize depend on the width
+// of the platform and a seeded generator must pick the same words on all
+// of them.
+pub fn sample_index<W, R>(word_list: &W, rng: &mut R) -> usize
+where
+    W: WordList + ?Sized,
+    R: Rng + ?Sized,
+{
+    Uniform::from(0..word_list.len() as u32).sample(rng) as usize
+}
+
+// One of the lists compiled into the program
+#[derive(Debug)]
+pub struct EmbeddedList {
+    name: &'static str,
+    language: &'static str,
+    source: &'static str,
+    words: &'static [&'static str],
+}
+
+pub static ORIGINAL: EmbeddedList = EmbeddedList {
+    name: "original",
+    language: "en",
+    source: "https://theworld.com/~reinhold/diceware.wordlist.asc",
+    words: &original::ORIGINAL_WORDLIST,
+};
+
+pub static EFF: EmbeddedList = EmbeddedList {
+    name: "eff",
+    language: "en",
+    source: "https://www.eff.org/files/2016/07/18/eff_large_wordlist.txt",
+    words: &eff::EFF_WORDLIST,
+};
+
+pub static BUILTIN_LISTS: &[&EmbeddedList] = &[&ORIGINAL, &EFF];
+
+// Finds a built-in list by its name or, failing that, the first one in the
+// given language.
+pub fn find_builtin_list(name: &str) -> Option<&'static EmbeddedList> {
+    let name = name.to_lowercase();
+    BUILTIN_LISTS
+        .iter()
+        .find(|l| l.name == name)
+        .or_else(|| BUILTIN_LISTS.iter().find(|l| l.language == name))
+        .copied()
+}
+
+impl WordList for EmbeddedList {
+    fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    fn word(&self, index: usize) -> Option<&str> {
+        self.words.get(index).copied()
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn language(&self) -> Option<&str> {
+        Some(self.language)
+    }
+
+    fn source(&self) -> Option<&str> {
+        Some(self.source)
+    }
+}
+
+impl WordList for Vec<String> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn word(&self, index: usize) -> Option<&str> {
+        self.get(index).map(String::as_str)
+    }
+
+    fn name(&self) -> &str {
+        "custom"
+    }
+}
+
+// The number of dice needed to pick a word from a list of `len` words, if
+// every word can be reached by exactly one roll.
+pub(crate) fn dice_count_for(len: usize) -> Option<usize> {
+    let mut dice_count = 0;
+    let mut size = 1;
+    while size < len {
+        size *= 6;
+        dice_count += 1;
+    }
+
+    (size == len && dice_count > 0).then_some(dice_count)
+}
+
+// The dice code of the word at `index` in a list ordered by dice roll,
+// e.g. with 5 dice 0 => "11111" and 7775 => "66666".
+pub(crate) fn dice_code(index: usize, dice_count: usize) -> String {
+    let mut code = vec![b'1'; dice_count];
+    let mut remainder = index;
+    for digit in code.iter_mut().rev() {
+        *digit += (remainder % 6) as u8;
+        remainder /= 6;
+    }
+
+    String::from_utf8(code).unwrap()
+}
+
+// The index a dice code names in a list ordered by dice roll, the reverse
+// of dice_code()
+pub(crate) fn dice_index(code: &str, dice_count: usize) -> Option<usize> {
+    if code.len() != dice_count {
+        return None;
+    }
+    let mut index = 0;
+    for b in code.bytes() {
+        if !(b'1'..=b'6').contains(&b) {
+            return None;
+        }
+        index = index * 6 + (b - b'1') as usize;
+    }
+
+    Some(index)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builtin_lists_are_in_dice_order() {
+        let cases = [
+            (&ORIGINAL, "11111", "a"),
+            (&ORIGINAL, "36355", "levi"),
+            (&ORIGINAL, "66666", "\"@"),
+            (&EFF, "11111", "abacus"),
+            (&EFF, "43634", "passing"),
+            (&EFF, "66666", "zoom"),
+        ];
+        for (list, code, expected) in cases {
+            assert_eq!(list.lookup_dice(code), Some(expected), "dice code {code}");
+        }
+    }
+
+    #[test]
+    fn builtin_list_metadata() {
+        assert_eq!(ORIGINAL.name(), "original");
+        assert_eq!(ORIGINAL.language(), Some("en"));
+        assert_eq!(EFF.name(), "eff");
+        assert_eq!(EFF.language(), Some("en"));
+        assert!(EFF.source().unwrap().contains("eff.org"));
+    }
+
+    #[test]
+    fn find_builtin_list_by_name_or_language() {
+        assert_eq!(find_builtin_list("eff").map(|l| l.name()), Some("eff"));
+        assert_eq!(find_builtin_list("EFF").map(|l| l.name()), Some("eff"));
+        assert_eq!(
+            find_builtin_list("original").map(|l| l.name()),
+            Some("original")
+        );
+        assert_eq!(find_builtin_list("en").map(|l| l.name()), Some("original"));
+        assert!(find_builtin_list("xx").is_none());
+    }
+
+    #[test]
+    fn dice_count_for_list_sizes() {
+        assert_eq!(dice_count_for(6), Some(1));
+        assert_eq!(dice_count_for(1296), Some(4));
+        assert_eq!(dice_count_for(7776), Some(5));
+        assert_eq!(dice_count_for(1), None);
+        assert_eq!(dice_count_for(2048), None);
+    }
+
+    #[test]
+    fn dice_view_of_6n_list() {
+        let list = &ORIGINAL;
+
+        assert_eq!(list.dice_count(), Some(5));
+        assert_eq!(list.lookup_dice("36355"), Some("levi"));
+        assert_eq!(list.dice_code(0).as_deref(), Some("11111"));
+        assert_eq!(list.dice_code(7775).as_deref(), Some("66666"));
+        assert_eq!(list.dice_code(7776), None);
+        assert_eq!(list.lookup_dice("3635"), None);
+        assert_eq!(list.lookup_dice("36375"), None);
+    }
+
+    #[test]
+    fn no_dice_view_of_arbitrary_list() {
+        let list: Vec<String> = ["one", "two", "three", "four", "five"]
+            .map(String::from)
+            .into();
+
+        assert_eq!(list.dice_count(), None);
+        assert_eq!(list.dice_code(0), None);
+        assert_eq!(list.lookup_dice("1"), None);
+        assert_eq!(list.word(4), Some("five"));
+        assert!((list.entropy_per_word() - 5f64.log2()).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn sample_index_is_uniform() {
+        let list: Vec<String> = ["a", "b", "c", "d", "e"].map(String::from).into();
+        let mut rng = rand::thread_rng();
+        let mut counts = [0usize; 5];
+        for _ in 0..50_000 {
+            counts[sample_index(&list, &mut rng)] += 1;
+        }
+
+        // each count is expected to be 10000 with a std. deviation of ~90
+        for count in counts {
+            assert!((9_500..10_500).contains(&count), "count = {count}");
+        }
+    }
+}
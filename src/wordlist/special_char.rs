@@ -6,3 +6,76 @@ pub const SPECIAL_CHARS: [[char; 6]; 6] = [
     ['?', '/', '0', '1', '2', '3'],
     ['4', '5', '6', '7', '8', '9'],
 ];
+
+// The sets --special-chars can name, besides "dice" for the table above.
+// shell-safe are the characters that mean nothing to a shell, zsh's `=cmd`
+// included, nor at the start of a YAML or INI value, and url-safe the
+// unreserved ones of RFC 3986.
+pub const SPECIAL_CHAR_SETS: [(&str, &str); 3] = [
+    ("shell-safe", "+,-./_"),
+    ("url-safe", "-._~"),
+    ("digits-only", "0123456789"),
+];
+
+// The characters of the set named `set`, or of `set` itself if it isn't a
+// name, without repeats so that each is as likely as the others. None is
+// the 6x6 table, in the order two dice pick from it.
+pub fn special_char_set(set: Option<&str>) -> Vec<char> {
+    let Some(set) = set.filter(|set| *set != "dice") else {
+        return SPECIAL_CHARS.iter().flatten().copied().collect();
+    };
+    let set = SPECIAL_CHAR_SETS
+        .iter()
+        .find(|(name, _)| *name == set)
+        .map_or(set, |(_, chars)| chars);
+
+    let mut chars = Vec::new();
+    for ch in set.chars() {
+        if !chars.contains(&ch) {
+            chars.push(ch);
+        }
+    }
+
+    chars
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dice_set_is_the_table() {
+        let dice = special_char_set(Some("dice"));
+
+        assert_eq!(dice.len(), 36);
+        assert_eq!(dice, special_char_set(None));
+        assert_eq!(dice[8], '(');
+    }
+
+    #[test]
+    fn named_sets() {
+        assert_eq!(special_char_set(Some("url-safe")), ['-', '.', '_', '~']);
+        assert_eq!(special_char_set(Some("digits-only")).len(), 10);
+        assert!(!special_char_set(Some("shell-safe"))
+            .iter()
+            .any(|ch| "\\\"'$`!*?&;|<>(){}[]# =:@%".contains(*ch)));
+    }
+
+    #[test]
+    fn shell_safe_chars_need_no_quotes() {
+        for ch in special_char_set(Some("shell-safe")) {
+            let word = format!("{ch}ab{ch}c{ch}");
+            let output = std::process::Command::new("sh")
+                .args(["-c", &format!("printf %s {word}")])
+                .output()
+                .unwrap();
+
+            assert_eq!(String::from_utf8_lossy(&output.stdout), word);
+        }
+    }
+
+    #[test]
+    fn other_sets_are_their_own_characters() {
+        assert_eq!(special_char_set(Some("!@!#@")), ['!', '@', '#']);
+    }
+}
@@ -1,12 +1,31 @@
+#![allow(clippy::manual_range_contains, clippy::vec_init_then_push)]
+
+use std::{collections::HashSet, path::PathBuf};
+
+use clap::Parser;
+use rand::{rngs::StdRng, thread_rng, SeedableRng};
 use sppg::{
-    add_capital_char, add_special_char, choose_word_list, cli::process_command_line, iterate,
-    lookup_word, passphrase::PassPhrase, print_passphrases, roll_dice_5_times,
-    roll_for_special_char,
+    add_capital_char, add_special_char,
+    check::{check, check_phrase},
+    choose_word_list,
+    cli::{process_command_line, Args},
+    crack::default_models,
+    decode::{decode, decode_phrase, Found},
+    dice::{passphrase_from_rolls, DiceError, Rolls},
+    explain_policy, format_passphrase, generate, generate_with_rng, insert_special_char, iterate,
+    lookup_word, max_length_warning,
+    passphrase::PassPhrase,
+    policy::Policy,
+    print_passphrases, roll_dice_n_times, roll_for_special_char, roll_word_index,
+    rules::parse_rules,
+    special_char_entropy, special_char_gaps,
+    wordlist::{special_char::special_char_set, WordList},
+    Error, Placement, RngSource, WordListError,
 };
 
 #[test]
 fn correct_digits() {
-    let lookup_num = roll_dice_5_times();
+    let lookup_num = roll_dice_n_times(5, &mut thread_rng());
 
     assert_eq!(lookup_num.len(), 5, "The lookup number is 5 digits long");
 
@@ -19,14 +38,180 @@ fn correct_digits() {
     }
 }
 
+#[test]
+fn correct_digits_for_dice_count() {
+    for count in [1, 4, 5, 8] {
+        let lookup_num = roll_dice_n_times(count, &mut thread_rng());
+
+        assert_eq!(
+            lookup_num.len(),
+            count,
+            "The lookup number is {count} digits long"
+        );
+    }
+}
+
+#[test]
+fn dice_count_and_entropy_of_builtin_lists() {
+    let mut args = process_command_line();
+    for eff in [false, true] {
+        args.eff = eff;
+        let word_list = choose_word_list(&args).unwrap();
+
+        assert_eq!(word_list.dice_count(), Some(5), "built-in lists use 5 dice");
+        assert!((word_list.entropy_per_word() - 12.925).abs() < 0.001);
+    }
+}
+
+#[test]
+fn iterate_with_four_dice_wordlist() {
+    let contents: String = (0..1296).map(|i| format!("word{i}\n")).collect();
+    let path = temp_word_list("four_dice", &contents);
+    let mut args = process_command_line();
+    args.wordlist = Some(path.clone());
+    let word_list = choose_word_list(&args).unwrap();
+
+    assert_eq!(
+        word_list.dice_count(),
+        Some(4),
+        "a 1296 word list uses 4 dice"
+    );
+    assert!((word_list.entropy_per_word() - 10.34).abs() < 0.001);
+
+    args.word_count = 6;
+    let list = iterate(&args).unwrap();
+    assert!(!list.is_empty());
+    for pp in list {
+        assert_eq!(pp.len(), 6, "words in passphrase = 6");
+        for i in 0..pp.len() {
+            assert!(pp[i].starts_with("word"));
+        }
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn iterate_with_arbitrary_length_wordlist() {
+    let contents: String = (0..2048).map(|i| format!("word{i}\n")).collect();
+    let path = temp_word_list("arbitrary_length", &contents);
+    let mut args = process_command_line();
+    args.wordlist = Some(path.clone());
+    let word_list = choose_word_list(&args).unwrap();
+
+    assert_eq!(word_list.len(), 2048);
+    assert_eq!(
+        word_list.dice_count(),
+        None,
+        "a 2048 word list can't use dice"
+    );
+    assert!((word_list.entropy_per_word() - 11.0).abs() < f64::EPSILON);
+
+    let list = iterate(&args).unwrap();
+    assert!(!list.is_empty());
+    std::fs::remove_file(path).unwrap();
+}
+
+// A word list file of its own for each test and each run, as tests run
+// side by side and so may runs of the suite
+fn temp_word_list(test: &str, contents: &str) -> PathBuf {
+    let name = format!("sppg_{test}_{}.txt", std::process::id());
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, contents).unwrap();
+
+    path
+}
+
+#[test]
+fn generate_from_vec_of_strings() {
+    let word_list: Vec<String> = (0..100).map(|i| format!("custom{i}")).collect();
+    let mut cli_args = process_command_line();
+    cli_args.word_count = 6;
+    let list = generate(&cli_args, &word_list).unwrap();
+
+    assert!(!list.is_empty());
+    for pp in list {
+        assert_eq!(pp.len(), 6, "words in passphrase = 6");
+        for i in 0..pp.len() {
+            assert!(word_list.contains(&pp[i]), "{} is in the list", pp[i]);
+        }
+    }
+}
+
+#[test]
+fn generate_from_user_defined_list() {
+    struct Numbers;
+    impl WordList for Numbers {
+        fn len(&self) -> usize {
+            1000
+        }
+
+        fn word(&self, index: usize) -> Option<&str> {
+            const WORDS: [&str; 10] = [
+                "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+            ];
+            WORDS
+                .get(index % 10)
+                .copied()
+                .filter(|_| index < self.len())
+        }
+
+        fn name(&self) -> &str {
+            "numbers"
+        }
+    }
+
+    let mut cli_args = process_command_line();
+    cli_args.word_count = 8;
+    let list = generate(&cli_args, &Numbers).unwrap();
+
+    assert!(!list.is_empty());
+    assert_eq!(Numbers.dice_count(), None);
+    assert!((Numbers.entropy_per_word() - 1000f64.log2()).abs() < f64::EPSILON);
+}
+
+#[test]
+fn wordlist_file_metadata() {
+    let mut args = process_command_line();
+    args.wordlist = Some("wordlists/eff_large_wordlist.txt".into());
+    let word_list = choose_word_list(&args).unwrap();
+
+    assert_eq!(word_list.name(), "eff_large_wordlist");
+    assert_eq!(word_list.language(), None);
+}
+
+#[test]
+fn word_index_is_in_range() {
+    let cli_args = process_command_line();
+    let word_list = choose_word_list(&cli_args).unwrap();
+    for _ in 0..1000 {
+        assert!(roll_word_index(word_list.as_ref(), &mut thread_rng()) < word_list.len());
+    }
+}
+
+#[test]
+fn lookup_index_retrieves_word() {
+    let cases = [(0, "a"), (7775, "\"@")];
+
+    let cli_args = process_command_line();
+    let word_list = choose_word_list(&cli_args).unwrap();
+    for (index, expected) in cases {
+        let word = lookup_word(index, word_list.as_ref());
+        assert_eq!(
+            word, expected,
+            "lookup index {}, expected {}",
+            index, expected
+        )
+    }
+}
+
 #[test]
 fn lookup_number_retrieves_word() {
     let cases = [("11111", "a"), ("36355", "levi"), ("66666", "\"@")];
 
     let cli_args = process_command_line();
-    let diceware_map = choose_word_list(&cli_args);
+    let word_list = choose_word_list(&cli_args).unwrap();
     for (index, expected) in cases {
-        let word = lookup_word(&index, &diceware_map);
+        let word = word_list.lookup_dice(index).unwrap();
         assert_eq!(
             word, expected,
             "lookup index {}, expected {}",
@@ -40,14 +225,9 @@ fn default_iterations_and_word_count() {
     let cli_args = process_command_line();
     let num_choices = cli_args.num_of_pass;
     let word_count = cli_args.word_count;
-    let list = iterate(&cli_args);
+    let list = iterate(&cli_args).unwrap();
 
-    // Because there may be insecure passphrases we can't test for equality
-    assert!(
-        list.len() <= 6,
-        "number of passphrases is <= {}",
-        num_choices
-    );
+    assert_eq!(list.len(), 6, "number of passphrases is {}", num_choices);
 
     for pp in list {
         assert_eq!(pp.len(), 5, "words in passphrase = {}", word_count);
@@ -61,26 +241,63 @@ fn iterations_and_word_count() {
     let mut cli_args = process_command_line();
     cli_args.num_of_pass = num_choices;
     cli_args.word_count = word_count;
-    let list = iterate(&cli_args);
+    let list = iterate(&cli_args).unwrap();
 
-    // Because there may be insecure passphrases we can't test for equality
-    assert!(
-        list.len() <= 12,
-        "number of passphrases is <= {}",
-        num_choices
-    );
+    assert_eq!(list.len(), 12, "number of passphrases is {}", num_choices);
 
     for pp in list {
         assert_eq!(pp.len(), 15, "words in passphrase = {}", word_count);
     }
 }
 
+#[test]
+fn entropy_of_plain_passphrases() {
+    let mut cli_args = process_command_line();
+    cli_args.word_count = 6;
+    let list = iterate(&cli_args).unwrap();
+
+    assert!(!list.is_empty());
+    for pp in list {
+        // 6 words from a 7776 word list
+        assert!((pp.entropy() - 6.0 * 7776f64.log2()).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn entropy_with_capital_and_special_char() {
+    let word_list: Vec<String> = ["alpha", "bravo", "charlie", "delta"]
+        .map(String::from)
+        .into();
+    let mut cli_args = process_command_line();
+    cli_args.word_count = 4;
+    cli_args.num_of_pass = 50;
+    cli_args.use_capital_char = true;
+    cli_args.use_special_char = true;
+    let list = generate(&cli_args, &word_list).unwrap();
+
+    assert!(!list.is_empty());
+    for pp in list {
+        // The letters and gaps depend on which words were chosen: each
+        // word has one more gap than it has letters
+        let output = format!("{}", pp);
+        let letters = output.chars().filter(|c| c.is_alphabetic()).count() as f64;
+        let gaps = letters + 4.0;
+        let expected = 4.0 * 2.0 + letters.log2() + special_char_entropy() + gaps.log2();
+
+        assert!(
+            (pp.entropy() - expected).abs() < 1e-9,
+            "{output}: {} != {expected}",
+            pp.entropy()
+        );
+    }
+}
+
 #[test]
 fn choose_wordlist_default() {
     let args = process_command_line();
-    let map = choose_word_list(&args);
+    let word_list = choose_word_list(&args).unwrap();
     assert_eq!(
-        lookup_word(&"11111", map),
+        word_list.lookup_dice("11111").unwrap(),
         "a",
         "default wordlist is the original one",
     );
@@ -90,26 +307,73 @@ fn choose_wordlist_default() {
 fn choose_wordlist_eff() {
     let mut args = process_command_line();
     args.eff = true;
-    let map = choose_word_list(&args);
+    let word_list = choose_word_list(&args).unwrap();
 
     assert_eq!(
-        lookup_word("11111", map),
+        word_list.lookup_dice("11111").unwrap(),
         "abacus",
         "when -e is used wordlist is the EFF one",
     );
 }
 
+#[test]
+fn choose_wordlist_from_file() {
+    let cases = [
+        ("wordlists/eff_large_wordlist.txt", "abacus"),
+        ("wordlists/diceware.wordlist.asc", "a"),
+    ];
+    for (path, expected) in cases {
+        let mut args = process_command_line();
+        args.wordlist = Some(path.into());
+        let word_list = choose_word_list(&args).unwrap();
+
+        assert_eq!(
+            word_list.lookup_dice("11111").unwrap(),
+            expected,
+            "when --wordlist is used wordlist is read from {}",
+            path
+        );
+    }
+}
+
+#[test]
+fn iterate_with_wordlist_file() {
+    let mut cli_args = process_command_line();
+    cli_args.wordlist = Some("wordlists/eff_large_wordlist.txt".into());
+    cli_args.word_count = 8;
+    let list = iterate(&cli_args).unwrap();
+
+    assert!(!list.is_empty());
+    for pp in list {
+        assert_eq!(pp.len(), 8, "words in passphrase = 8");
+    }
+}
+
+#[test]
+fn missing_wordlist_file_is_error() {
+    let mut cli_args = process_command_line();
+    cli_args.wordlist = Some("wordlists/does_not_exist.txt".into());
+    let result = iterate(&cli_args);
+
+    assert!(matches!(result, Err(Error::WordList(WordListError::Io(_)))));
+}
+
 #[test]
 fn special_char_handling() {
     let mut cli_args = process_command_line();
     cli_args.num_of_pass = 1;
     cli_args.word_count = 7;
-    let mut list = iterate(&cli_args);
+    let mut list = iterate(&cli_args).unwrap();
     assert!(!list.is_empty());
-    let special_char = roll_for_special_char();
-    let new_pp = add_special_char(&mut list[0], special_char);
+    let special_char = roll_for_special_char(&mut thread_rng());
+    add_special_char(
+        &mut list[0],
+        special_char,
+        Placement::Word,
+        &mut thread_rng(),
+    );
 
-    let output = format!("{}", new_pp);
+    let output = format!("{}", list[0]);
     assert!(
         output.contains(special_char),
         "the passphrase contains a special char"
@@ -123,9 +387,115 @@ fn no_insecure_passphrases() {
     let mut cli_args = process_command_line();
     cli_args.num_of_pass = num_choices;
     cli_args.word_count = word_count;
-    let list = iterate(&cli_args);
+    cli_args.policy = Policy::Legacy;
+    let result = iterate(&cli_args);
+
+    assert!(
+        matches!(
+            result,
+            Err(Error::TooManyAttempts {
+                wanted: 1,
+                found: 0,
+                attempts: 100
+            })
+        ),
+        "no passphrase is secure enough"
+    );
+}
+
+#[test]
+fn legacy_policy_returns_requested_number() {
+    let mut cli_args = process_command_line();
+    cli_args.num_of_pass = 50;
+    cli_args.word_count = 4;
+    cli_args.policy = Policy::Legacy;
+    let list = iterate(&cli_args).unwrap();
+
+    // Some 4 word phrases are too short, so they have to be replaced
+    assert_eq!(list.len(), 50, "number of passphrases is 50");
+    for pp in list {
+        assert!(!pp.is_insecure());
+    }
+}
+
+#[test]
+fn legacy_policy_reports_entropy_after_rejection() {
+    let mut cli_args = process_command_line();
+    cli_args.word_count = 5;
+    cli_args.policy = Policy::Legacy;
+    let list = iterate(&cli_args).unwrap();
+    let words = 5.0 * 7776f64.log2();
+
+    // The 0.6% of phrases that are too short cost about 0.009 bits
+    for pp in list {
+        let lost = words - pp.entropy();
+        assert!((0.008..0.009).contains(&lost), "lost = {lost}");
+    }
+}
+
+#[test]
+fn explain_legacy_policy() {
+    let mut cli_args = process_command_line();
+    cli_args.word_count = 5;
+    cli_args.policy = Policy::Legacy;
+    let text = explain_policy(&cli_args).unwrap();
+
+    assert!(text.contains("word count: 5\n"), "{text}");
+    assert!(
+        text.contains("rejected: 0.59% of phrases (exact)\n"),
+        "{text}"
+    );
+    assert!(
+        text.contains("entropy after the policy: at least 64.6 bits\n"),
+        "{text}"
+    );
+}
+
+#[test]
+fn entropy_policy_keeps_every_passphrase() {
+    let mut cli_args = process_command_line();
+    cli_args.num_of_pass = 20;
+    cli_args.word_count = 1;
+    let list = iterate(&cli_args).unwrap();
+
+    assert_eq!(list.len(), 20, "no passphrase is thrown away");
+}
 
-    assert_eq!(list.len(), 0, "list of passphrases is empty");
+#[test]
+fn min_entropy_is_met_by_construction() {
+    let cases = [
+        (40, false, false, 4),
+        (60, false, false, 5),
+        (60, true, true, 5),
+        (59, false, true, 4),
+        (100, false, false, 8),
+    ];
+    for (target, capital, special, expected_words) in cases {
+        let mut cli_args = process_command_line();
+        cli_args.num_of_pass = 20;
+        cli_args.min_entropy = Some(target);
+        cli_args.use_capital_char = capital;
+        cli_args.use_special_char = special;
+        let list = iterate(&cli_args).unwrap();
+
+        assert_eq!(list.len(), 20, "no passphrase is thrown away");
+        for pp in list {
+            let words = if special {
+                format!("{}", pp).split(' ').count()
+            } else {
+                pp.len()
+            };
+            assert_eq!(
+                words, expected_words,
+                "{target} bits needs {expected_words} words"
+            );
+            assert!(
+                pp.entropy() >= target as f64,
+                "{} >= {target}",
+                pp.entropy()
+            );
+        }
+    }
 }
 
 #[test]
@@ -135,10 +505,10 @@ fn capital_char_handling() {
     pp.push("phrase");
     let mut list = Vec::<PassPhrase>::new();
     list.push(pp);
-    let new_pp = add_capital_char(&mut list[0]);
+    add_capital_char(&mut list[0], &mut thread_rng());
 
     let mut contains_capital = false;
-    let output = format!("{}", new_pp);
+    let output = format!("{}", list[0]);
     for (_, ch) in output.char_indices() {
         if ch.is_uppercase() {
             contains_capital = true;
@@ -155,10 +525,8 @@ fn quality_for_short_phrases() {
     cli_args.use_capital_char = true;
     cli_args.use_special_char = true;
     cli_args.word_count = 2;
-    let mut list = iterate(&cli_args);
-    while list.is_empty() {
-        list = iterate(&cli_args);
-    }
+    cli_args.policy = Policy::Legacy;
+    let list = iterate(&cli_args).unwrap();
     let pp = &list[0];
 
     // contains capital
@@ -193,13 +561,13 @@ fn special_char_in_first_word() {
     // assume 500 tries is enough to get at least one special char in the first word
     for _ in 0..500 {
         // Wordlist contains numbers so skip them to avoid false positives
-        let mut ch = roll_for_special_char();
+        let mut ch = roll_for_special_char(&mut thread_rng());
         while ch >= '0' && ch <= '9' {
-            ch = roll_for_special_char();
+            ch = roll_for_special_char(&mut thread_rng());
         }
-        let new_pp = add_special_char(&mut list[0], ch);
+        add_special_char(&mut list[0], ch, Placement::Word, &mut thread_rng());
 
-        let output = new_pp[0].clone();
+        let output = list[0][0].clone();
         for (_, ch) in output.char_indices() {
             if ch.is_ascii_punctuation() {
                 contains_special_char = true;
@@ -215,18 +583,18 @@ fn special_char_in_first_word() {
 
 #[test]
 fn special_char_in_first_character() {
-    let mut pp = PassPhrase::new(None);
-    pp.push("some");
-    pp.push("phrase");
-    let mut list = Vec::<PassPhrase>::new();
-    list.push(pp);
     let mut contains_special_char = false;
     // assume 500 tries is enough to get at least one special char in the first word
     for _ in 0..500 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("some");
+        pp.push("phrase");
+        let mut list = Vec::<PassPhrase>::new();
+        list.push(pp);
         let ch = '*';
-        let new_pp = add_special_char(&mut list[0], ch);
+        add_special_char(&mut list[0], ch, Placement::Word, &mut thread_rng());
 
-        let output = new_pp[0].clone();
+        let output = list[0][0].clone();
         if output[0..1] == ch.to_string() {
             contains_special_char = true;
         }
@@ -238,6 +606,296 @@ fn special_char_in_first_character() {
     );
 }
 
+#[test]
+fn special_char_gaps_are_uniform() {
+    // "ab c" has the gaps |a|b| |c| so each of the 5 is chosen 1/5 of the time
+    let expected = ["*ab c", "a*b c", "ab* c", "ab *c", "ab c*"];
+    let mut counts = [0usize; 5];
+    for _ in 0..50_000 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("ab").push("c");
+        let bits = add_special_char(&mut pp, '*', Placement::Word, &mut thread_rng());
+
+        assert_eq!(bits, Some(5f64.log2()));
+        let output = format!("{}", pp);
+        counts[expected.iter().position(|e| *e == output).unwrap()] += 1;
+    }
+
+    // each count is expected to be 10000 with a std. deviation of ~90
+    for count in counts {
+        assert!((9_500..10_500).contains(&count), "count = {count}");
+    }
+}
+
+#[test]
+fn special_chars_of_a_set_are_uniform() {
+    // 7 characters can't be picked with whole dice, yet each is as likely
+    let set = "!@#$%^&";
+    let cli_args = Args::parse_from(["sppg", "-e", "-w", "1", "-n", "250", "--special-chars", set]);
+    let mut counts = [0usize; 7];
+    for _ in 0..40 {
+        for pp in iterate(&cli_args).unwrap() {
+            let phrase = pp.to_string();
+            let found: Vec<usize> = phrase.chars().filter_map(|ch| set.find(ch)).collect();
+
+            assert_eq!(found.len(), 1, "{phrase}");
+            assert!(
+                (pp.entropy() - (7776f64.log2() + 7f64.log2() + (phrase.len() as f64).log2()))
+                    .abs()
+                    < 1e-9
+            );
+            counts[found[0]] += 1;
+        }
+    }
+
+    // each count is expected to be ~1429 with a std. deviation of ~35
+    for count in counts {
+        assert!((1_250..1_610).contains(&count), "count = {count}");
+    }
+}
+
+#[test]
+fn specials_go_into_distinct_gaps() {
+    // Any two gaps of a word have a letter between them
+    let cli_args = Args::parse_from([
+        "sppg",
+        "-e",
+        "-w",
+        "1",
+        "-n",
+        "200",
+        "--specials",
+        "2",
+        "--special-chars",
+        "*",
+    ]);
+    for pp in iterate(&cli_args).unwrap() {
+        let phrase = pp.to_string();
+        let gaps = phrase.len() - 1;
+        let choices = (gaps * (gaps - 1) / 2) as f64;
+
+        assert_eq!(phrase.matches('*').count(), 2, "{phrase}");
+        assert!(!phrase.contains("**"), "{phrase}");
+        assert!((pp.entropy() - (7776f64.log2() + choices.log2())).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn generated_phrases_have_the_counts_asked_for() {
+    let cli_args = Args::parse_from([
+        "sppg",
+        "-e",
+        "-w",
+        "3",
+        "-n",
+        "100",
+        "--capitals",
+        "3",
+        "--specials",
+        "2",
+        "--digits",
+        "2",
+        "--special-chars",
+        "!#$%",
+        "--special-placement",
+        "any",
+    ]);
+    for pp in iterate(&cli_args).unwrap() {
+        let phrase = pp.to_string();
+        let count = |f: fn(&char) -> bool| phrase.chars().filter(f).count();
+
+        assert_eq!(count(char::is_ascii_uppercase), 3, "{phrase}");
+        assert_eq!(count(char::is_ascii_digit), 2, "{phrase}");
+        assert_eq!(count(|ch| "!#$%".contains(*ch)), 2, "{phrase}");
+    }
+}
+
+#[test]
+fn case_styles_shape_each_word() {
+    let words_of = |case: &str| {
+        let cli_args = Args::parse_from(["sppg", "-e", "-w", "4", "-n", "50", "--case", case]);
+        iterate(&cli_args).unwrap()
+    };
+    let word_bits = 4.0 * 7776f64.log2();
+
+    for pp in words_of("title") {
+        assert!(
+            pp.to_string()
+                .split(' ')
+                .all(|w| w[..1] == w[..1].to_uppercase() && w[1..] == w[1..].to_lowercase()),
+            "{pp}"
+        );
+        assert!((pp.entropy() - word_bits).abs() < 1e-9);
+    }
+    for pp in words_of("camel") {
+        let phrase = pp.to_string();
+        assert!(!phrase.contains(' '), "{phrase}");
+        assert_eq!(
+            phrase.chars().filter(char::is_ascii_uppercase).count(),
+            4,
+            "{phrase}"
+        );
+    }
+    for pp in words_of("upper-word") {
+        let upper = pp
+            .to_string()
+            .split(' ')
+            .filter(|w| *w == w.to_uppercase())
+            .count();
+        assert_eq!(upper, 1, "{pp}");
+        assert!((pp.entropy() - (word_bits + 2.0)).abs() < 1e-9);
+    }
+    for pp in words_of("random") {
+        assert!(
+            pp.to_string()
+                .split(' ')
+                .all(|w| *w == w.to_uppercase() || *w == w.to_lowercase()),
+            "{pp}"
+        );
+        assert!((pp.entropy() - (word_bits + 4.0)).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn each_gap_makes_a_distinct_phrase() {
+    for mut pp in [PassPhrase::new(None), PassPhrase::joined()] {
+        pp.push("Abacus").push("Abdomen");
+        for placement in [Placement::Word, Placement::Token, Placement::Any] {
+            let count = special_char_gaps(&pp, placement);
+            let phrases: HashSet<String> = (0..count)
+                .map(|n| {
+                    let mut pp = pp.clone();
+                    insert_special_char(&mut pp, '~', placement, n);
+                    pp.to_string()
+                })
+                .collect();
+
+            assert_eq!(phrases.len(), count, "'{pp}' with {placement:?}");
+        }
+    }
+}
+
+#[test]
+fn camel_case_counts_each_gap_once() {
+    // "AbacusAbdomen" has 14 gaps, and the 6th and 7th differ
+    let cli_args = dice_args(&[
+        "-e",
+        "-w",
+        "2",
+        "--case",
+        "camel",
+        "-s",
+        "--special-placement",
+        "any",
+    ]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let phrase = |rolls: &str| {
+        let mut rolls = Rolls::new(rolls.as_bytes(), None::<Vec<u8>>);
+        passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap()
+    };
+    let pp = phrase("11111 11112 11 21");
+
+    assert_eq!(pp.to_string(), "Abacus~Abdomen");
+    assert_eq!(phrase("11111 11112 11 22").to_string(), "AbacusA~bdomen");
+    let expected = 2.0 * 7776f64.log2() + 36f64.log2() + 14f64.log2();
+    assert!((pp.entropy() - expected).abs() < 1e-9);
+}
+
+#[test]
+fn camel_case_fits_in_max_length() {
+    // With no separators the 4 words have all 20 characters to themselves
+    let cli_args = Args::parse_from([
+        "sppg",
+        "-e",
+        "-w",
+        "4",
+        "--case",
+        "camel",
+        "--max-length",
+        "20",
+    ]);
+
+    for pp in iterate(&cli_args).unwrap() {
+        assert!(pp.to_string().chars().count() <= 20, "{pp}");
+        assert!(pp.entropy() < 4.0 * 7776f64.log2());
+    }
+}
+
+#[test]
+fn check_agrees_with_case_styles() {
+    for case in ["title", "camel", "upper-word", "random"] {
+        let cli_args = Args::parse_from(["sppg", "-e", "-w", "5", "-n", "50", "--case", case]);
+        let word_list = choose_word_list(&cli_args).unwrap();
+        for pp in iterate(&cli_args).unwrap() {
+            let strength = check(
+                word_list.as_ref(),
+                &pp.to_string(),
+                None,
+                &special_char_set(None),
+            );
+
+            // A random phrase may look like one of the other styles, which
+            // cost less, but never more than it was
+            assert!(strength.entropy() <= pp.entropy() + 1e-9, "{case} {pp}");
+            if case != "random" {
+                assert!(
+                    (strength.entropy() - pp.entropy()).abs() < 1e-9,
+                    "{case} {pp}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn special_char_as_token() {
+    let expected = ["* ab c", "ab * c", "ab c *"];
+    for _ in 0..200 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("ab").push("c");
+        let bits = add_special_char(&mut pp, '*', Placement::Token, &mut thread_rng());
+
+        assert_eq!(bits, Some(3f64.log2()));
+        assert!(expected.contains(&&format!("{}", pp)[..]));
+    }
+}
+
+#[test]
+fn special_char_anywhere() {
+    let expected = [
+        "*ab c", "a*b c", "ab* c", "ab *c", "ab c*", "* ab c", "ab * c", "ab c *",
+    ];
+    let mut seen = [false; 8];
+    for _ in 0..1000 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("ab").push("c");
+        let bits = add_special_char(&mut pp, '*', Placement::Any, &mut thread_rng());
+
+        assert_eq!(bits, Some(3.0), "8 gaps are 3 bits");
+        seen[expected
+            .iter()
+            .position(|e| *e == format!("{}", pp))
+            .unwrap()] = true;
+    }
+
+    assert!(seen.iter().all(|s| *s), "every gap is used");
+}
+
+#[test]
+fn special_char_in_empty_phrase() {
+    let mut pp = PassPhrase::new(None);
+
+    assert_eq!(
+        add_special_char(&mut pp, '*', Placement::Word, &mut thread_rng()),
+        None
+    );
+    assert_eq!(
+        add_special_char(&mut pp, '*', Placement::Token, &mut thread_rng()),
+        Some(0.0)
+    );
+    assert_eq!(format!("{}", pp), "*");
+}
+
 #[test]
 fn capital_in_first_word() {
     let mut pp = PassPhrase::new(None);
@@ -248,9 +906,9 @@ fn capital_in_first_word() {
     let mut contains_special_char = false;
     // assume 500 tries is enough to get at least one special char in the first word
     for _ in 0..500 {
-        let new_pp = add_capital_char(&mut list[0]);
+        add_capital_char(&mut list[0], &mut thread_rng());
 
-        let output = new_pp[0].clone();
+        let output = list[0][0].clone();
         for (_, ch) in output.char_indices() {
             if ch.is_uppercase() {
                 contains_special_char = true;
@@ -264,6 +922,186 @@ fn capital_in_first_word() {
     );
 }
 
+#[test]
+fn capital_char_in_accented_words() {
+    for _ in 0..500 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("été").push("ñu");
+        add_capital_char(&mut pp, &mut thread_rng());
+
+        let output = format!("{}", pp);
+        assert!(
+            ["Été", "éTé", "étÉ"].contains(&&pp[0][..]) || ["Ñu", "ñU"].contains(&&pp[1][..]),
+            "one accented letter is capitalized: {output}"
+        );
+    }
+}
+
+#[test]
+fn special_char_in_accented_words() {
+    for _ in 0..500 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("été").push("ñu");
+        add_special_char(&mut pp, '*', Placement::Word, &mut thread_rng());
+
+        let output = format!("{}", pp);
+        assert_eq!(output.chars().count(), 7, "one char is inserted: {output}");
+        assert_eq!(output.replace('*', ""), "été ñu", "no letters are lost");
+    }
+}
+
+#[test]
+fn capital_char_never_deletes_characters() {
+    for _ in 0..200 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("1st").push("\"@").push("a&p");
+        let bits = add_capital_char(&mut pp, &mut thread_rng());
+
+        let output = format!("{}", pp);
+        assert_eq!(
+            output.to_lowercase(),
+            "1st \"@ a&p",
+            "nothing is lost: {output}"
+        );
+        assert!(
+            ["1St", "1sT"].contains(&&pp[0][..]) || ["A&p", "a&P"].contains(&&pp[2][..]),
+            "one letter is capitalized: {output}"
+        );
+        assert_eq!(bits, Some(2.0), "one of 4 letters is chosen");
+    }
+}
+
+#[test]
+fn capital_char_without_letters() {
+    let mut pp = PassPhrase::new(None);
+    pp.push("\"@").push("007").push("漢字");
+    let bits = add_capital_char(&mut pp, &mut thread_rng());
+
+    assert_eq!(bits, None, "there is no letter to capitalize");
+    assert_eq!(format!("{}", pp), "\"@ 007 漢字", "the phrase is unchanged");
+}
+
+#[test]
+fn capital_char_is_uniform_over_letters() {
+    // "ab" has 2 letters and "c" has 1, so if a word was chosen first "c"
+    // would be capitalized half of the time instead of a third
+    let mut counts = [0usize; 3];
+    for _ in 0..30_000 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("ab").push("c");
+        add_capital_char(&mut pp, &mut thread_rng());
+
+        let output = format!("{}", pp);
+        let idx = output.find(char::is_uppercase).unwrap();
+        counts[output[..idx].replace(' ', "").len()] += 1;
+    }
+
+    // each count is expected to be 10000 with a std. deviation of ~82
+    for count in counts {
+        assert!((9_500..10_500).contains(&count), "count = {count}");
+    }
+}
+
+#[test]
+fn capital_char_expands_sharp_s() {
+    let mut pp = PassPhrase::new(None);
+    pp.push("ß");
+    add_capital_char(&mut pp, &mut thread_rng());
+
+    assert_eq!(pp[0], "SS", "'ß' is capitalized as \"SS\"");
+}
+
+#[test]
+fn capital_char_in_cyrillic_words() {
+    for _ in 0..200 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("мир").push("кот");
+        add_capital_char(&mut pp, &mut thread_rng());
+
+        let output = format!("{}", pp);
+        assert_eq!(output.to_lowercase(), "мир кот", "no letters are lost");
+        assert_eq!(
+            output.chars().filter(|c| c.is_uppercase()).count(),
+            1,
+            "one letter is capitalized: {output}"
+        );
+    }
+}
+
+#[test]
+fn capital_char_keeps_combining_marks() {
+    // "e" followed by U+0301 COMBINING ACUTE ACCENT
+    for _ in 0..200 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("cafe\u{301}");
+        add_capital_char(&mut pp, &mut thread_rng());
+
+        assert!(
+            ["Cafe\u{301}", "cAfe\u{301}", "caFe\u{301}", "cafE\u{301}"].contains(&&pp[0][..]),
+            "the accent stays on its letter: {}",
+            pp[0]
+        );
+    }
+}
+
+#[test]
+fn capital_char_in_cjk_words() {
+    for _ in 0..200 {
+        let mut pp = PassPhrase::new(None);
+        pp.push("漢字").push("かな");
+        add_capital_char(&mut pp, &mut thread_rng());
+
+        assert_eq!(format!("{}", pp), "漢字 かな", "CJK has no case");
+    }
+}
+
+#[test]
+fn special_char_in_unicode_words() {
+    let cases = ["漢字", "мир", "cafe\u{301}", "ラウトは難しいです！"];
+    for word in cases {
+        for _ in 0..200 {
+            let mut pp = PassPhrase::new(None);
+            pp.push(word);
+            add_special_char(&mut pp, '*', Placement::Word, &mut thread_rng());
+
+            let output = format!("{}", pp);
+            assert_eq!(output.replace('*', ""), word, "nothing is lost from {word}");
+            assert!(
+                !output.contains("*\u{301}"),
+                "the accent stays on its letter: {output}"
+            );
+        }
+    }
+}
+
+#[test]
+fn choose_wordlist_by_name_or_language() {
+    let cases = [("original", "a"), ("eff", "abacus"), ("en", "a")];
+    for (name, expected) in cases {
+        let mut args = process_command_line();
+        args.list = Some(name.into());
+        let word_list = choose_word_list(&args).unwrap();
+
+        assert_eq!(
+            word_list.lookup_dice("11111").unwrap(),
+            expected,
+            "--list {name} selects a built-in list"
+        );
+    }
+}
+
+#[test]
+fn choose_unknown_wordlist_is_error() {
+    let mut args = process_command_line();
+    args.list = Some("xx".into());
+    let err = choose_word_list(&args).err().unwrap();
+
+    assert_eq!(
+        err.to_string(),
+        "no built-in word list named 'xx' (available: original, eff)"
+    );
+}
+
 #[test]
 fn printed_correctly() {
     let mut pp = PassPhrase::new(None);
@@ -271,7 +1109,10 @@ fn printed_correctly() {
     pp.push("phrase");
     let mut list = Vec::<PassPhrase>::new();
     list.push(pp);
-    print_passphrases(&list);
+    let mut cli_args = process_command_line();
+    print_passphrases(&list, &cli_args, &[]);
+    cli_args.show_entropy = true;
+    print_passphrases(&list, &cli_args, &[]);
 
     assert_eq!(1, 1);
 }
@@ -298,3 +1139,532 @@ fn separator_char_in_output() {
 
     assert_eq!(display, "some-phrase", "the separator '-' is displayed")
 }
+
+#[test]
+fn os_rng_generates_passphrases() {
+    let mut cli_args = process_command_line();
+    cli_args.rng = RngSource::Os;
+    cli_args.quality = true;
+    let list = iterate(&cli_args).unwrap();
+
+    assert_eq!(list.len(), 6, "number of passphrases is 6");
+}
+
+#[test]
+fn same_seed_same_passphrases() {
+    let mut cli_args = process_command_line();
+    cli_args.use_capital_char = true;
+    cli_args.use_special_char = true;
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut phrases = Vec::new();
+    for seed in [7, 7, 8] {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let list = generate_with_rng(&cli_args, word_list.as_ref(), &mut rng).unwrap();
+        phrases.push(list.iter().map(|pp| pp.to_string()).collect::<Vec<_>>());
+    }
+
+    assert_eq!(phrases[0], phrases[1], "a seeded rng repeats its phrases");
+    assert_ne!(phrases[0], phrases[2], "another seed gives other phrases");
+}
+
+#[test]
+fn seeded_phrases_match_golden_vectors() {
+    let vectors = include_str!("vectors.txt");
+    let mut checked = 0;
+    for block in vectors.split("\n\n").skip(1) {
+        let mut lines = block.lines();
+        let command = lines.next().unwrap().strip_prefix("$ ").unwrap();
+        let expected: Vec<&str> = lines.collect();
+        let cli_args = Args::parse_from(command.split_whitespace());
+        let list = iterate(&cli_args).unwrap();
+        let output: Vec<String> = list
+            .iter()
+            .map(|pp| format_passphrase(pp, &cli_args, &[]))
+            .flat_map(|text| text.lines().map(String::from).collect::<Vec<_>>())
+            .collect();
+
+        assert_eq!(output, expected, "output of `{command}`");
+        checked += 1;
+    }
+
+    assert_eq!(checked, 15, "every vector is checked");
+}
+
+fn dice_args(args: &[&str]) -> Args {
+    Args::parse_from(["sppg", "dice"].iter().chain(args))
+}
+
+#[test]
+fn dice_rolls_pick_several_capitals() {
+    // The 1st of 6 letters, then the 1st of the 5 still in lower case
+    let cli_args = dice_args(&["-e", "-w", "1", "--capitals", "2"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut rolls = Rolls::new("11111 1 1".as_bytes(), None::<Vec<u8>>);
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+    assert_eq!(pp.to_string(), "ABacus");
+    assert_eq!(pp.rolls()[1].label, "capital letter 1 of 6");
+    assert_eq!(pp.rolls()[2].label, "capital letter 1 of 5");
+    assert!((pp.entropy() - (7776f64.log2() + 15f64.log2())).abs() < 1e-9);
+}
+
+#[test]
+fn dice_rolls_pick_the_case_of_each_word() {
+    // A roll past the 2 choices is rolled again
+    let cli_args = dice_args(&["-e", "-w", "2", "--case", "random"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut rolls = Rolls::new("11111 11112 2 5 1".as_bytes(), None::<Vec<u8>>);
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+    assert_eq!(pp.to_string(), "ABACUS abdomen");
+    assert_eq!(pp.rolls()[2].label, "word 1 in upper case");
+    assert_eq!(pp.rolls()[3].label, "word 2 in lower case");
+    assert!((pp.entropy() - (2.0 * 7776f64.log2() + 2.0)).abs() < 1e-9);
+}
+
+#[test]
+fn dice_rolls_pick_words() {
+    let cli_args = dice_args(&["-w", "4"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut rolls = Rolls::new("11111 36355\n66666\n\n43634\n".as_bytes(), None::<Vec<u8>>);
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+    assert_eq!(pp.to_string(), "a levi \"@ norm");
+    assert!((pp.entropy() - 4.0 * 7776f64.log2()).abs() < 1e-9);
+}
+
+#[test]
+fn dice_rolls_pick_capital_and_special_char() {
+    // The 13th of 15 letters, SPECIAL_CHARS[1][2] and the last of 22 gaps
+    let cli_args = dice_args(&["-q"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut rolls = Rolls::new(
+        "11111 36355 66666 43634 12345 31 23 44".as_bytes(),
+        None::<Vec<u8>>,
+    );
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+    assert_eq!(pp.to_string(), "a levi \"@ norm apaThy(");
+}
+
+#[test]
+fn dice_rolls_past_last_choice_are_rolled_again() {
+    // 4 letters need 1 die, and a 5 or a 6 is rolled again
+    let cli_args = dice_args(&["-w", "1", "-c", "-e"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut rolls = Rolls::new("66666 6 5 2".as_bytes(), None::<Vec<u8>>);
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+    assert_eq!(pp.to_string(), "zOom");
+}
+
+#[test]
+fn dice_rolls_reject_bad_input() {
+    let cli_args = dice_args(&["-w", "2"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let cases = [
+        ("11111 1111", "roll '1111' has 4 dice, expected 5"),
+        ("11111 111111", "roll '111111' has 6 dice, expected 5"),
+        (
+            "11111 11171",
+            "invalid roll '11171': every die must be 1 to 6",
+        ),
+        (
+            "11111 abcde",
+            "invalid roll 'abcde': every die must be 1 to 6",
+        ),
+        (
+            "11111",
+            "the rolls ran out before the passphrase was complete",
+        ),
+    ];
+    for (input, message) in cases {
+        let mut rolls = Rolls::new(input.as_bytes(), None::<Vec<u8>>);
+        let err = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap_err();
+
+        assert_eq!(err.to_string(), message, "input '{input}'");
+    }
+}
+
+#[test]
+fn dice_rolls_need_a_list_for_dice() {
+    let cli_args = dice_args(&["-w", "2"]);
+    let word_list: Vec<String> = ["one", "two", "three"].map(String::from).into();
+    let mut rolls = Rolls::new("1 2".as_bytes(), None::<Vec<u8>>);
+    let err = passphrase_from_rolls(&cli_args, &word_list, &mut rolls).unwrap_err();
+
+    assert!(matches!(err, Error::Dice(DiceError::NoDice { len: 3, .. })));
+}
+
+#[test]
+fn dice_prompt_asks_again_after_bad_roll() {
+    let cli_args = dice_args(&["-w", "2"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut prompt = Vec::<u8>::new();
+    let mut rolls = Rolls::new("11111\n1234 5\n36355\n".as_bytes(), Some(&mut prompt));
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+    assert_eq!(pp.to_string(), "a levi");
+    assert_eq!(
+        String::from_utf8(prompt).unwrap(),
+        "word 1 of 2, roll 5 dice: \
+         word 2 of 2, roll 5 dice: \
+         error: roll '1234' has 4 dice, expected 5\n\
+         word 2 of 2, roll 5 dice: "
+    );
+}
+
+#[test]
+fn shown_rolls_rebuild_the_phrase() {
+    for placement in ["word", "token", "any"] {
+        let mut cli_args = dice_args(&["-q", "--special-placement", placement, "--show-rolls"]);
+        cli_args.num_of_pass = 20;
+        let word_list = choose_word_list(&cli_args).unwrap();
+        let list = iterate(&cli_args).unwrap();
+        for pp in list {
+            let codes: Vec<&str> = pp.rolls().iter().map(|r| r.code.as_str()).collect();
+            let input = codes.join(" ");
+            let mut rolls = Rolls::new(input.as_bytes(), None::<Vec<u8>>);
+            let rebuilt = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+            assert_eq!(rebuilt.to_string(), pp.to_string(), "rolls {input}");
+            assert_eq!(rebuilt.rolls(), pp.rolls());
+            assert!((rebuilt.entropy() - pp.entropy()).abs() < 1e-9);
+        }
+    }
+}
+
+#[test]
+fn empty_list_is_an_error() {
+    let cli_args = process_command_line();
+    let err = generate_with_rng(&cli_args, &Vec::<String>::new(), &mut thread_rng()).unwrap_err();
+
+    assert!(matches!(
+        err,
+        Error::WordList(WordListError::TooShort { found: 0 })
+    ));
+}
+
+#[test]
+fn rolls_are_only_kept_when_shown() {
+    let mut cli_args = Args::parse_from(["sppg", "-q", "--case", "random"]);
+    let list = iterate(&cli_args).unwrap();
+    assert!(list.iter().all(|pp| pp.rolls().is_empty()));
+
+    cli_args.show_rolls = true;
+    let list = iterate(&cli_args).unwrap();
+    assert!(list.iter().all(|pp| pp.rolls().len() > pp.len()));
+}
+
+#[test]
+fn rolls_of_list_without_dice() {
+    let mut cli_args = process_command_line();
+    cli_args.show_rolls = true;
+    let word_list: Vec<String> = ["one", "two", "three"].map(String::from).into();
+    let list = generate(&cli_args, &word_list).unwrap();
+    let pp = &list[0];
+
+    assert_eq!(pp.rolls().len(), pp.len());
+
+    for (roll, word) in pp.rolls().iter().zip(pp.to_string().split(' ')) {
+        let index = word_list.iter().position(|w| w == word).unwrap();
+        assert_eq!(roll.code, format!("#{}", index + 1));
+        assert_eq!(roll.label, word);
+    }
+}
+
+#[test]
+fn generated_phrases_decode_to_their_rolls() {
+    // None of the EFF words has a capital or a special character of its
+    // own, so every transform can be undone without doubt
+    let mut cli_args = process_command_line();
+    cli_args.eff = true;
+    cli_args.quality = true;
+    cli_args.use_capital_char = true;
+    cli_args.use_special_char = true;
+    cli_args.show_rolls = true;
+    cli_args.num_of_pass = 50;
+    let word_list = choose_word_list(&cli_args).unwrap();
+    for pp in iterate(&cli_args).unwrap() {
+        let decoded = decode(
+            word_list.as_ref(),
+            &pp.to_string(),
+            None,
+            &special_char_set(None),
+        );
+        let codes: Vec<&str> = decoded
+            .iter()
+            .map(|d| match &d.found {
+                Found::Word { code, .. } => code.as_str(),
+                _ => panic!("{} is not a word of the list", d.token),
+            })
+            .collect();
+        let rolls: Vec<&str> = pp.rolls()[..5].iter().map(|r| r.code.as_str()).collect();
+
+        assert_eq!(codes, rolls, "phrase {pp}");
+    }
+}
+
+#[test]
+fn digits_are_undone_with_each_special_char_set() {
+    for set in [None, Some("shell-safe"), Some("url-safe")] {
+        for seed in 0..8 {
+            let seed = seed.to_string();
+            let mut args = vec!["sppg", "-e", "-s", "--digits", "1", "--seed", &seed];
+            args.extend(set.iter().flat_map(|set| ["--special-chars", set]));
+            let cli_args = Args::parse_from(args);
+            for pp in iterate(&cli_args).unwrap() {
+                let phrase = pp.to_string();
+                for decoded in decode_phrase(&cli_args, &phrase).unwrap() {
+                    assert!(
+                        !matches!(decoded.found, Found::NotInList),
+                        "{} of {phrase} with {set:?} is not in the list",
+                        decoded.token
+                    );
+                }
+                assert_eq!(check_phrase(&cli_args, &phrase).unwrap().unknown, 0);
+            }
+        }
+    }
+}
+
+#[test]
+fn check_agrees_with_generated_entropy() {
+    // With the EFF list every transform can be undone, so the attacker who
+    // knows the structure faces exactly the choices that were made
+    let mut cli_args = process_command_line();
+    cli_args.eff = true;
+    cli_args.use_capital_char = true;
+    cli_args.use_special_char = true;
+    cli_args.capitals = Some(2);
+    cli_args.specials = Some(2);
+    cli_args.special_chars = Some("digits-only".into());
+    cli_args.num_of_pass = 50;
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let digits = special_char_set(Some("digits-only"));
+    for pp in iterate(&cli_args).unwrap() {
+        let strength = check(word_list.as_ref(), &pp.to_string(), None, &digits);
+
+        assert!(
+            (strength.entropy() - pp.entropy()).abs() < 1e-9,
+            "phrase {pp}: {} != {}",
+            strength.entropy(),
+            pp.entropy()
+        );
+    }
+}
+
+#[test]
+fn crack_times_under_phrase() {
+    let mut cli_args = process_command_line();
+    cli_args.show_crack_time = true;
+    let mut pp = PassPhrase::new(None);
+    pp.push("some").push("phrase").add_entropy(51.0);
+    let text = format_passphrase(&pp, &cli_args, &default_models());
+
+    // 2^50 guesses at 10^11 a second take 11259 seconds
+    assert_eq!(
+        text,
+        "some phrase\n\
+         \x20   online-throttled   1.3e9 years\n\
+         \x20   offline-fast-hash  3 hours\n\
+         \x20   offline-bcrypt     3568 years\n\
+         \x20   offline-argon2id   35678 years"
+    );
+}
+
+#[test]
+fn generated_phrases_follow_rules() {
+    let rules = "minlength: 12; maxlength: 32; required: lower; required: upper; required: digit; allowed: [-_.]";
+    let mut cli_args = Args::parse_from(["sppg", "-w", "4", "--rules", rules]);
+    cli_args.num_of_pass = 30;
+    let list = iterate(&cli_args).unwrap();
+    let rules = parse_rules(rules).unwrap();
+
+    assert_eq!(list.len(), 30);
+    for pp in list {
+        let phrase = pp.to_string();
+        assert_eq!(rules.broken_by(&phrase), None, "{phrase}");
+        assert_eq!(phrase.matches('-').count(), 3, "{phrase}");
+    }
+}
+
+#[test]
+fn words_are_upper_case_when_rules_allow_no_lower_case() {
+    let rules = "minlength: 12; maxlength: 32; required: upper; required: digit; allowed: [-_.]";
+    let cli_args = Args::parse_from(["sppg", "-w", "4", "--rules", rules]);
+    let list = iterate(&cli_args).unwrap();
+
+    for pp in list {
+        let phrase = pp.to_string();
+        assert!(!phrase.chars().any(char::is_lowercase), "{phrase}");
+        assert!(phrase.chars().any(|ch| ch.is_ascii_digit()), "{phrase}");
+    }
+}
+
+#[test]
+fn unsatisfiable_rules_are_named() {
+    let cases = [
+        (
+            vec!["-w", "8", "--rules", "maxlength: 10"],
+            "password rule 'maxlength: 10' can't be met: the shortest phrase of 8 words is 15 characters",
+        ),
+        (
+            vec!["--rules", "max-consecutive: 0"],
+            "password rule 'max-consecutive: 0' can't be met: it threw away all 20000 phrases of 5 words tried",
+        ),
+        (
+            vec!["-S", "_", "--rules", "allowed: lower, [-]"],
+            "password rule 'allowed: lower, [-]' can't be met: the separator '_' isn't allowed",
+        ),
+        (
+            vec!["--rules", "required: uppr"],
+            "invalid password rule 'required: uppr': unknown character class 'uppr'",
+        ),
+    ];
+    for (args, message) in cases {
+        let cli_args = Args::parse_from(["sppg"].iter().chain(&args));
+        let err = iterate(&cli_args).unwrap_err();
+
+        assert!(matches!(err, Error::Rules(_)));
+        assert_eq!(err.to_string(), message, "args {args:?}");
+    }
+}
+
+#[test]
+fn dice_rolls_add_what_rules_require() {
+    // A digit, the 2nd of 10, before the first of 15 letters
+    let cli_args = dice_args(&[
+        "-e",
+        "-w",
+        "2",
+        "--rules",
+        "required: digit; allowed: lower, [ -]",
+    ]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut rolls = Rolls::new("11111 11112 12 11".as_bytes(), None::<Vec<u8>>);
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+    assert_eq!(pp.to_string(), "1abacus abdomen");
+    assert_eq!(pp.rolls()[2].label, "digit 1");
+}
+
+#[test]
+fn dice_rolls_of_words_the_rules_take_out() {
+    // "a&p" is rolled again; the words keep the rolls of the printed list
+    let cli_args = dice_args(&["-w", "2", "--rules", "allowed: lower, [ ]"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut rolls = Rolls::new("11111 11112".as_bytes(), None::<Vec<u8>>);
+    let err = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "the word of roll '11112' has characters the rules don't allow"
+    );
+
+    let mut prompt = Vec::<u8>::new();
+    let mut rolls = Rolls::new("11111\n11112\n11114\n".as_bytes(), Some(&mut prompt));
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+
+    assert_eq!(pp.to_string(), "a aa");
+    let codes: Vec<&str> = pp.rolls().iter().map(|r| r.code.as_str()).collect();
+    assert_eq!(codes, ["11111", "11114"]);
+    assert!(String::from_utf8(prompt)
+        .unwrap()
+        .contains("error: the word of roll '11112' has characters the rules don't allow\n"));
+}
+
+#[test]
+fn phrases_fit_in_max_length() {
+    let cases = [
+        vec!["-w", "4", "--max-length", "20"],
+        vec!["-q", "--special-placement", "any", "--max-length", "24"],
+        vec!["-e", "-w", "3", "-S", "-", "--max-length", "16"],
+        vec![
+            "-w",
+            "6",
+            "--max-length",
+            "32",
+            "--rules",
+            "required: upper; allowed: digit, [-]",
+        ],
+    ];
+    for args in cases {
+        let mut cli_args = Args::parse_from(["sppg"].iter().chain(&args));
+        cli_args.num_of_pass = 50;
+        let max = cli_args.max_length.unwrap() as usize;
+        let list = iterate(&cli_args).unwrap();
+
+        assert_eq!(list.len(), 50);
+        for pp in list {
+            let phrase = pp.to_string();
+            assert!(phrase.chars().count() <= max, "{phrase} with {args:?}");
+        }
+    }
+}
+
+#[test]
+fn max_length_entropy_counts_the_words_that_fit() {
+    // 7 characters for 2 words: any of the 6 words first, then those that
+    // fit in what is left
+    let words: Vec<String> = (0..6)
+        .map(|len| ((b'a' + len) as char).to_string().repeat(len as usize + 1))
+        .collect();
+    let mut cli_args = Args::parse_from(["sppg", "-w", "2", "--max-length", "8"]);
+    cli_args.num_of_pass = 50;
+    let list = generate_with_rng(&cli_args, &words, &mut thread_rng()).unwrap();
+
+    for pp in list {
+        let first = pp[0].len();
+        let expected = 6f64.log2() + ((7 - first).min(6) as f64).log2();
+
+        assert!(pp.to_string().len() <= 8, "{pp}");
+        assert!((pp.entropy() - expected).abs() < 1e-9, "{pp}");
+    }
+}
+
+#[test]
+fn max_length_shorter_than_any_phrase_is_error() {
+    let cli_args = Args::parse_from(["sppg", "-w", "8", "--max-length", "10"]);
+    let err = iterate(&cli_args).unwrap_err();
+
+    assert!(matches!(err, Error::MaxLength { .. }));
+    assert_eq!(
+        err.to_string(),
+        "--max-length 10 is too short: the shortest phrase of 8 words is 15 characters"
+    );
+}
+
+#[test]
+fn max_length_warns_when_entropy_drops() {
+    let cli_args = Args::parse_from(["sppg", "-w", "4", "--max-length", "20"]);
+    let warning = max_length_warning(&cli_args).unwrap().unwrap();
+
+    assert!(warning.contains("as little as 43.0 bits"), "{warning}");
+    assert!(warning.contains("(51.7 without it)"), "{warning}");
+
+    // Long enough that no phrase is cut short
+    let cli_args = Args::parse_from(["sppg", "-w", "4", "--max-length", "40"]);
+    assert_eq!(max_length_warning(&cli_args).unwrap(), None);
+    // Cut short, but still above the threshold
+    let cli_args = Args::parse_from(["sppg", "-w", "6", "--max-length", "30"]);
+    assert_eq!(max_length_warning(&cli_args).unwrap(), None);
+}
+
+#[test]
+fn dice_rolls_too_long_for_max_length() {
+    // "abacus" leaves 3 characters, too few for "abdomen"
+    let cli_args = dice_args(&["-e", "-w", "2", "--max-length", "10"]);
+    let word_list = choose_word_list(&cli_args).unwrap();
+    let mut rolls = Rolls::new("11111 11112".as_bytes(), None::<Vec<u8>>);
+    let err = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "the word of roll '11112' is longer than the 3 characters left for it"
+    );
+
+    let mut rolls = Rolls::new("11111 11364".as_bytes(), None::<Vec<u8>>);
+    let pp = passphrase_from_rolls(&cli_args, word_list.as_ref(), &mut rolls).unwrap();
+    assert_eq!(pp.to_string(), "abacus aim");
+}